@@ -0,0 +1,144 @@
+use crate::{
+    framebuffer::Pixel, Alignment, AlphaFormat, ColorSpace, FrameBufferDesc, Pixely, PixelyDesc,
+    PixelyError, ScaleMode, ScalingFilter, WindowDesc,
+};
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use wgpu::{Adapter, AddressMode, CompositeAlphaMode, Device, Instance, PresentMode, Queue};
+
+pub struct PixelyBuilder<'a, W> {
+    window: Option<(&'a W, usize, usize)>,
+    scale_factor: f64,
+    framebuffer_size: Option<(usize, usize)>,
+    present_mode: PresentMode,
+    alpha_mode: CompositeAlphaMode,
+    clear_color: Pixel,
+    scaling_filter: ScalingFilter,
+    address_mode: AddressMode,
+    scale_mode: ScaleMode,
+    alignment: Alignment,
+    color_space: ColorSpace,
+    alpha_format: AlphaFormat,
+    sample_count: u32,
+    shader: Option<&'a str>,
+}
+impl<'a, W: HasRawWindowHandle + HasRawDisplayHandle> PixelyBuilder<'a, W> {
+    pub(crate) fn new() -> Self {
+        Self {
+            window: None,
+            scale_factor: 1.0,
+            framebuffer_size: None,
+            present_mode: PresentMode::Fifo,
+            alpha_mode: CompositeAlphaMode::Opaque,
+            clear_color: Pixel::black(),
+            scaling_filter: ScalingFilter::default(),
+            address_mode: AddressMode::ClampToEdge,
+            scale_mode: ScaleMode::default(),
+            alignment: Alignment::default(),
+            color_space: ColorSpace::default(),
+            alpha_format: AlphaFormat::default(),
+            sample_count: 1,
+            shader: None,
+        }
+    }
+
+    pub fn window(mut self, window: &'a W, width: usize, height: usize) -> Self {
+        self.window = Some((window, width, height));
+        self
+    }
+    pub fn scale_factor(mut self, scale_factor: f64) -> Self {
+        self.scale_factor = scale_factor;
+        self
+    }
+    pub fn framebuffer_size(mut self, width: usize, height: usize) -> Self {
+        self.framebuffer_size = Some((width, height));
+        self
+    }
+    pub fn present_mode(mut self, mode: PresentMode) -> Self {
+        self.present_mode = mode;
+        self
+    }
+    pub fn alpha_mode(mut self, mode: CompositeAlphaMode) -> Self {
+        self.alpha_mode = mode;
+        self
+    }
+    pub fn clear_color(mut self, color: Pixel) -> Self {
+        self.clear_color = color;
+        self
+    }
+    pub fn scaling_filter(mut self, filter: ScalingFilter) -> Self {
+        self.scaling_filter = filter;
+        self
+    }
+    pub fn address_mode(mut self, mode: AddressMode) -> Self {
+        self.address_mode = mode;
+        self
+    }
+    pub fn scale_mode(mut self, mode: ScaleMode) -> Self {
+        self.scale_mode = mode;
+        self
+    }
+    pub fn alignment(mut self, alignment: Alignment) -> Self {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+    pub fn alpha_format(mut self, format: AlphaFormat) -> Self {
+        self.alpha_format = format;
+        self
+    }
+    pub fn sample_count(mut self, count: u32) -> Self {
+        self.sample_count = count;
+        self
+    }
+    /// See [`PixelyDesc::shader`].
+    pub fn shader(mut self, wgsl: &'a str) -> Self {
+        self.shader = Some(wgsl);
+        self
+    }
+
+    pub fn build(
+        self,
+        instance: &Instance,
+        adapter: &Adapter,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<Pixely, PixelyError> {
+        let (window, width, height) = self.window.ok_or(PixelyError::MissingField("window"))?;
+        let (buffer_width, buffer_height) = self
+            .framebuffer_size
+            .ok_or(PixelyError::MissingField("framebuffer_size"))?;
+
+        let mut pixely = Pixely::new(PixelyDesc {
+            window: WindowDesc {
+                window,
+                width,
+                height,
+                scale_factor: self.scale_factor,
+                present_mode: self.present_mode,
+                alpha_mode: self.alpha_mode,
+            },
+            buffer: FrameBufferDesc {
+                width: buffer_width,
+                height: buffer_height,
+            },
+            scaling_filter: self.scaling_filter,
+            address_mode: self.address_mode,
+            scale_mode: self.scale_mode,
+            color_space: self.color_space,
+            alpha_format: self.alpha_format,
+            sample_count: self.sample_count,
+            shader: self.shader,
+            instance,
+            adapter,
+            device,
+            queue,
+        })?;
+        pixely.set_clear_color(self.clear_color);
+        pixely.set_alignment(self.alignment);
+        Ok(pixely)
+    }
+}
@@ -0,0 +1,119 @@
+use crate::framebuffer::{OutOfBounds, Pixel};
+use std::ops::Range;
+
+/// Anything pixels can be drawn onto, such as a [`crate::framebuffer::FrameBuffer`] or a
+/// [`crate::view::FrameBufferViewMut`] into one.
+///
+/// Implementors only need to provide pixel access; the drawing primitives are built on it.
+pub trait Canvas {
+    fn width(&self) -> usize;
+    fn height(&self) -> usize;
+    fn get_pixel_checked(&self, x: usize, y: usize) -> Option<Pixel>;
+    fn set_pixel_checked(&mut self, x: usize, y: usize, pixel: Pixel) -> Result<(), OutOfBounds>;
+
+    /// Sets the pixels in `xs` of row `y`, which the caller has already clipped to the canvas.
+    /// Override this when a whole span can be written faster than pixel by pixel.
+    fn fill_row(&mut self, y: usize, xs: Range<usize>, pixel: Pixel) {
+        for x in xs {
+            let _ = self.set_pixel_checked(x, y, pixel);
+        }
+    }
+
+    /// Fills a rectangle, clipped to the canvas.
+    fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, pixel: Pixel) {
+        let x_end = x.saturating_add(w).min(self.width());
+        let y_end = y.saturating_add(h).min(self.height());
+        if x >= x_end || y >= y_end {
+            return;
+        }
+
+        for row in y..y_end {
+            self.fill_row(row, x..x_end, pixel);
+        }
+    }
+    /// Draws the one pixel thick border of a rectangle, clipped to the canvas.
+    fn draw_rect(&mut self, x: usize, y: usize, w: usize, h: usize, pixel: Pixel) {
+        if w == 0 || h == 0 {
+            return;
+        }
+
+        let right = x.saturating_add(w - 1);
+        let bottom = y.saturating_add(h - 1);
+        self.fill_rect(x, y, w, 1, pixel);
+        if h > 1 {
+            self.fill_rect(x, bottom, w, 1, pixel);
+        }
+        // The sides skip the corners the top and bottom edges already drew.
+        let side_height = h.saturating_sub(2);
+        self.fill_rect(x, y.saturating_add(1), 1, side_height, pixel);
+        if w > 1 {
+            self.fill_rect(right, y.saturating_add(1), 1, side_height, pixel);
+        }
+    }
+    /// Draws a line including both endpoints, clipped to the canvas.
+    fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, pixel: Pixel) {
+        let (mut x, mut y) = (x0 as i64, y0 as i64);
+        let (x1, y1) = (x1 as i64, y1 as i64);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let step_x = if x < x1 { 1 } else { -1 };
+        let step_y = if y < y1 { 1 } else { -1 };
+
+        let mut error = dx + dy;
+        loop {
+            if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+                let _ = self.set_pixel_checked(x, y, pixel);
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+    /// Fills a polygon with the even-odd rule, clipped to the canvas. The last point connects
+    /// back to the first. With fewer than three points, the line between them is drawn instead.
+    fn fill_polygon(&mut self, points: &[(i32, i32)], pixel: Pixel) {
+        if points.len() < 3 {
+            if let (Some(&(x0, y0)), Some(&(x1, y1))) = (points.first(), points.last()) {
+                self.draw_line(x0, y0, x1, y1, pixel);
+            }
+            return;
+        }
+
+        let (width, height) = (self.width() as f64, self.height() as i64);
+        let top = points.iter().map(|p| p.1).min().unwrap().max(0) as i64;
+        let bottom = (points.iter().map(|p| p.1).max().unwrap() as i64).min(height);
+        let edges = points.iter().zip(points.iter().cycle().skip(1));
+
+        // A pixel is filled if its center lies inside the polygon.
+        let mut crossings = Vec::new();
+        for y in top..bottom {
+            let center = y as f64 + 0.5;
+            crossings.clear();
+            for (&(x0, y0), &(x1, y1)) in edges.clone() {
+                let (x0, y0, x1, y1) = (x0 as f64, y0 as f64, x1 as f64, y1 as f64);
+                if (y0 <= center) != (y1 <= center) {
+                    crossings.push(x0 + (center - y0) * (x1 - x0) / (y1 - y0));
+                }
+            }
+            crossings.sort_by(f64::total_cmp);
+
+            for span in crossings.chunks_exact(2) {
+                let start = (span[0] - 0.5).ceil().clamp(0.0, width) as usize;
+                let end = (span[1] - 0.5).ceil().clamp(0.0, width) as usize;
+                if start < end {
+                    self.fill_row(y as usize, start..end, pixel);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Reinterpreting slices of plain data as bytes and back, through bytemuck when it's enabled or
+//! an equivalent, much smaller, set of helpers when it isn't.
+
+#[cfg(feature = "bytemuck")]
+pub(crate) use bytemuck::{cast_slice, cast_slice_mut, pod_read_unaligned};
+#[cfg(not(feature = "bytemuck"))]
+pub(crate) use plain::{cast_slice, cast_slice_mut, pod_read_unaligned, Plain};
+
+#[cfg(not(feature = "bytemuck"))]
+mod plain {
+    use std::mem::size_of;
+
+    /// The guarantees of bytemuck's `Pod`: any bit pattern is a valid value, and there's no
+    /// padding, so values can be read from and written as arbitrary bytes.
+    ///
+    /// # Safety
+    ///
+    /// Only implement this for `repr(C)` or primitive types that uphold the above.
+    pub(crate) unsafe trait Plain: Copy + 'static {}
+    unsafe impl Plain for u8 {}
+    unsafe impl Plain for u16 {}
+    unsafe impl Plain for u32 {}
+    unsafe impl Plain for u64 {}
+    unsafe impl Plain for f32 {}
+    unsafe impl<T: Plain, const N: usize> Plain for [T; N] {}
+
+    /// Panics if `a` isn't aligned for `B`, or its length in bytes isn't a multiple of `B`'s
+    /// size, like `bytemuck::cast_slice`.
+    fn cast_len<A: Plain, B: Plain>(a: &[A]) -> usize {
+        assert!(
+            a.as_ptr().cast::<B>().is_aligned(),
+            "slice isn't aligned for the target type"
+        );
+        let bytes = std::mem::size_of_val(a);
+        assert!(
+            bytes.is_multiple_of(size_of::<B>()),
+            "slice length doesn't fit the target type"
+        );
+        bytes / size_of::<B>()
+    }
+    pub(crate) fn cast_slice<A: Plain, B: Plain>(a: &[A]) -> &[B] {
+        let len = cast_len::<A, B>(a);
+        // SAFETY: both types are `Plain`, and the pointer is aligned for `B` and covers
+        // exactly `len` values of it.
+        unsafe { std::slice::from_raw_parts(a.as_ptr().cast(), len) }
+    }
+    pub(crate) fn cast_slice_mut<A: Plain, B: Plain>(a: &mut [A]) -> &mut [B] {
+        let len = cast_len::<A, B>(a);
+        // SAFETY: as in `cast_slice`, and the borrow of `a` is moved into the result.
+        unsafe { std::slice::from_raw_parts_mut(a.as_mut_ptr().cast(), len) }
+    }
+    pub(crate) fn pod_read_unaligned<T: Plain>(bytes: &[u8]) -> T {
+        assert_eq!(
+            bytes.len(),
+            size_of::<T>(),
+            "byte count doesn't match the type"
+        );
+        // SAFETY: `T` is `Plain`, and `bytes` holds exactly one value of it.
+        unsafe { bytes.as_ptr().cast::<T>().read_unaligned() }
+    }
+}
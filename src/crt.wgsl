@@ -0,0 +1,23 @@
+// Prepended by `crt_shader`:
+// const SCANLINE_INTENSITY: f32;
+// const CURVATURE: f32;
+
+fn barrel(uv: vec2<f32>) -> vec2<f32> {
+    let centered = uv * 2.0 - 1.0;
+    let bent = centered + centered * (centered.yx * centered.yx) * CURVATURE;
+    return bent * 0.5 + 0.5;
+}
+
+@fragment
+fn fragment_main(input: PostInput) -> @location(0) vec4<f32> {
+    let uv = barrel(input.uv);
+    // Sampled before the bounds check, as sampling must happen in uniform control flow.
+    let color = textureSample(input_texture, input_sampler, uv);
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0));
+
+    // Darken every other row of the target.
+    let line = 0.5 + 0.5 * cos(uv.y * uniforms.resolution.y * 3.14159265);
+    let shade = 1.0 - SCANLINE_INTENSITY * line;
+    let shaded = vec4<f32>(color.rgb * shade, color.a);
+    return select(vec4<f32>(0.0, 0.0, 0.0, 1.0), shaded, inside);
+}
@@ -1,30 +1,804 @@
-use bytemuck::{cast_slice, Pod, Zeroable};
-use std::iter::once;
+use crate::{
+    canvas::Canvas,
+    cast::{cast_slice, cast_slice_mut},
+    text::{self, Glyph, GLYPH_HEIGHT, GLYPH_WIDTH},
+    view::{FrameBufferView, FrameBufferViewMut},
+};
+#[cfg(any(feature = "png", feature = "image"))]
+use std::path::Path;
+use std::{
+    error::Error,
+    fmt,
+    iter::once,
+    mem::size_of,
+    ops::{Index, IndexMut, Range},
+};
+#[cfg(feature = "png")]
+use std::{
+    fs::File,
+    io::{self, BufWriter},
+};
 
+#[derive(Clone)]
 pub struct FrameBuffer {
     width: usize,
     height: usize,
     pixels: Box<[Pixel]>,
+    dirty: Option<DirtyRegion>,
+    dirty_rows: Box<[bool]>,
 }
 impl FrameBuffer {
     pub(super) fn new(width: usize, height: usize) -> Self {
-        Self {
-            pixels: once(Pixel::black()).cycle().take(width * height).collect(),
+        Self::try_new(width, height).unwrap_or_else(|e| panic!("{e}"))
+    }
+    /// Creates a black buffer, failing if its size in bytes doesn't fit in a `usize`.
+    pub fn try_new(width: usize, height: usize) -> Result<FrameBuffer, SizeOverflow> {
+        let len = pixel_count(width, height)?;
+        let pixels = once(Pixel::black()).cycle().take(len).collect();
+        Ok(Self::from_boxed(width, height, pixels))
+    }
+    pub fn from_pixels(
+        width: usize,
+        height: usize,
+        pixels: Vec<Pixel>,
+    ) -> Result<FrameBuffer, SizeError> {
+        check_len(pixel_count(width, height)?, pixels.len())?;
+        Ok(Self::from_boxed(width, height, pixels.into_boxed_slice()))
+    }
+    /// Copies tightly packed RGBA8 data, four bytes per pixel.
+    pub fn from_bytes(width: usize, height: usize, bytes: &[u8]) -> Result<FrameBuffer, SizeError> {
+        check_len(
+            pixel_count(width, height)? * size_of::<Pixel>(),
+            bytes.len(),
+        )?;
+        Ok(Self::from_boxed(width, height, pixels_from_bytes(bytes)))
+    }
+    fn from_boxed(width: usize, height: usize, pixels: Box<[Pixel]>) -> Self {
+        let mut framebuffer = Self {
+            pixels,
             width,
             height,
-        }
+            dirty: None,
+            dirty_rows: once(false).cycle().take(height).collect(),
+        };
+        framebuffer.mark_all_dirty();
+        framebuffer
     }
 
+    pub fn get_pixel(&self, x: usize, y: usize) -> Pixel {
+        self.pixels[self.index_or_panic(x, y)]
+    }
+    pub fn get_pixel_checked(&self, x: usize, y: usize) -> Option<Pixel> {
+        self.checked_index(x, y).map(|i| self.pixels[i])
+    }
     pub fn set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
-        let i = self.coord_to_index(x, y);
-
-        if self.pixels.len() != 0 {
+        self.set_pixel_checked(x, y, pixel)
+            .unwrap_or_else(|e| panic!("{e}"));
+    }
+    pub fn set_pixel_checked(
+        &mut self,
+        x: usize,
+        y: usize,
+        pixel: Pixel,
+    ) -> Result<(), OutOfBounds> {
+        let i = self.bounds_check(x, y)?;
+        self.pixels[i] = pixel;
+        self.mark_dirty(x, y, 1, 1);
+        Ok(())
+    }
+    /// Writes a batch of pixels, skipping any out of bounds, and returns the bounding box of
+    /// those written as `(x, y, width, height)`.
+    pub fn set_pixels(&mut self, pixels: &[((usize, usize), Pixel)]) -> Option<Rect> {
+        let mut bounds: Option<DirtyRegion> = None;
+        for &((x, y), pixel) in pixels {
+            let Ok(i) = self.bounds_check(x, y) else {
+                continue;
+            };
             self.pixels[i] = pixel;
+            self.dirty_rows[y] = true;
+            let point = DirtyRegion::point(x, y);
+            bounds = Some(bounds.map_or(point, |bounds| bounds.union(point)));
+        }
+
+        let bounds = bounds?;
+        self.dirty = Some(self.dirty.map_or(bounds, |dirty| dirty.union(bounds)));
+        Some(bounds.rect())
+    }
+    /// Like [`FrameBuffer::set_pixels`], but writes nothing if any coordinates are out of
+    /// bounds, returning all of them instead.
+    pub fn try_set_pixels(
+        &mut self,
+        pixels: &[((usize, usize), Pixel)],
+    ) -> Result<Option<Rect>, Vec<OutOfBounds>> {
+        let out_of_bounds: Vec<_> = pixels
+            .iter()
+            .filter_map(|&((x, y), _)| self.bounds_check(x, y).err())
+            .collect();
+        if !out_of_bounds.is_empty() {
+            return Err(out_of_bounds);
+        }
+        Ok(self.set_pixels(pixels))
+    }
+    pub fn blend_pixel(&mut self, x: usize, y: usize, src: Pixel) {
+        let i = self.index_or_panic(x, y);
+        self.pixels[i] = src.over(self.pixels[i]);
+        self.mark_dirty(x, y, 1, 1);
+    }
+    pub fn set_pixel_masked(&mut self, x: usize, y: usize, pixel: Pixel, mask: ChannelMask) {
+        let i = self.index_or_panic(x, y);
+        self.pixels[i] = mask.apply(self.pixels[i], pixel);
+        self.mark_dirty(x, y, 1, 1);
+    }
+    pub fn fill(&mut self, pixel: Pixel) {
+        self.pixels.fill(pixel);
+        self.mark_all_dirty();
+    }
+    pub fn clear(&mut self) {
+        self.fill(Pixel::black());
+    }
+    pub fn fill_rect(&mut self, x: usize, y: usize, w: usize, h: usize, pixel: Pixel) {
+        Canvas::fill_rect(self, x, y, w, h, pixel);
+    }
+    pub fn draw_rect(&mut self, x: usize, y: usize, w: usize, h: usize, pixel: Pixel) {
+        Canvas::draw_rect(self, x, y, w, h, pixel);
+    }
+    pub fn draw_line(&mut self, x0: i32, y0: i32, x1: i32, y1: i32, pixel: Pixel) {
+        Canvas::draw_line(self, x0, y0, x1, y1, pixel);
+    }
+    pub fn fill_polygon(&mut self, points: &[(i32, i32)], pixel: Pixel) {
+        Canvas::fill_polygon(self, points, pixel);
+    }
+    /// Draws an anti-aliased line with Xiaolin Wu's algorithm, blending `color` into the
+    /// buffer by how much of each pixel the line covers. Pixel centers are at whole numbers.
+    pub fn draw_line_aa(&mut self, x0: f32, y0: f32, x1: f32, y1: f32, color: Pixel) {
+        if ![x0, y0, x1, y1].iter().all(|c| c.is_finite()) {
+            return;
+        }
+
+        let steep = (y1 - y0).abs() > (x1 - x0).abs();
+        let (x0, y0, x1, y1) = match steep {
+            true => (y0, x0, y1, x1),
+            false => (x0, y0, x1, y1),
+        };
+        let (x0, y0, x1, y1) = match x0 > x1 {
+            true => (x1, y1, x0, y0),
+            false => (x0, y0, x1, y1),
+        };
+        let dx = x1 - x0;
+        let gradient = if dx == 0.0 { 1.0 } else { (y1 - y0) / dx };
+        let fract = |v: f32| v - v.floor();
+        let extent = match steep {
+            true => self.height,
+            false => self.width,
+        } as f32;
+        let mut plot = |major: i64, minor: i64, coverage: f32| match steep {
+            true => self.blend_coverage_clipped(minor, major, color, coverage),
+            false => self.blend_coverage_clipped(major, minor, color, coverage),
+        };
+
+        // The endpoints are only partially covered along the major axis.
+        let mut endpoint = |x: f32, y: f32, gap: f32| {
+            let x_end = x.round();
+            let y_end = y + gradient * (x_end - x);
+            let minor = y_end.floor();
+            plot(x_end as i64, minor as i64, (1.0 - fract(y_end)) * gap);
+            plot(x_end as i64, minor as i64 + 1, fract(y_end) * gap);
+            (x_end, y_end)
+        };
+        let (first_x, first_y) = endpoint(x0, y0, 1.0 - fract(x0 + 0.5));
+        let (last_x, _) = endpoint(x1, y1, fract(x1 + 0.5));
+
+        // Only step through the part of the major axis that can land inside the buffer.
+        let start = (first_x + 1.0).max(0.0) as i64;
+        let end = last_x.min(extent) as i64;
+        for major in start..end {
+            let y = first_y + gradient * (major as f32 - first_x);
+            let minor = y.floor() as i64;
+            plot(major, minor, 1.0 - fract(y));
+            plot(major, minor + 1, fract(y));
+        }
+    }
+    fn blend_coverage_clipped(&mut self, x: i64, y: i64, color: Pixel, coverage: f32) {
+        let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) else {
+            return;
+        };
+        if x < self.width && y < self.height {
+            let alpha = (color.alpha as f32 * coverage).round() as u8;
+            self.blend_pixel(x, y, Pixel { alpha, ..color });
+        }
+    }
+    pub fn draw_circle(&mut self, cx: i32, cy: i32, radius: i32, pixel: Pixel) {
+        for (x, y) in circle_octant(radius) {
+            for (dx, dy) in [(x, y), (y, x), (-y, x), (-x, y)] {
+                let (cx, cy, dx, dy) = (cx as i64, cy as i64, dx as i64, dy as i64);
+                self.set_pixel_clipped(cx + dx, cy + dy, pixel);
+                self.set_pixel_clipped(cx - dx, cy - dy, pixel);
+            }
+        }
+    }
+    pub fn fill_circle(&mut self, cx: i32, cy: i32, radius: i32, pixel: Pixel) {
+        let (cx, cy) = (cx as i64, cy as i64);
+        for (x, y) in circle_octant(radius) {
+            let (x, y) = (x as i64, y as i64);
+            self.fill_span_clipped(cx - x, cx + x, cy + y, pixel);
+            self.fill_span_clipped(cx - x, cx + x, cy - y, pixel);
+            self.fill_span_clipped(cx - y, cx + y, cy + x, pixel);
+            self.fill_span_clipped(cx - y, cx + y, cy - x, pixel);
+        }
+    }
+    /// Fills the ellipse with radii `rx` and `ry` around `(cx, cy)`. A radius of 0 gives a line
+    /// one pixel thick, or a single pixel if both are.
+    pub fn fill_ellipse(&mut self, cx: i32, cy: i32, rx: i32, ry: i32, pixel: Pixel) {
+        let (cx, cy) = (cx as i64, cy as i64);
+        // Only the rows within the buffer, as distances above and below the center.
+        let (top, bottom) = (-cy, self.height as i64 - 1 - cy);
+        let rows = top.max(-bottom).max(0)..bottom.max(-top) + 1;
+        let first = rows.start;
+        for (i, half_width) in ellipse_half_widths(rx, ry, rows).into_iter().enumerate() {
+            let (y, half_width) = (first + i as i64, half_width as i64);
+            self.fill_span_clipped(cx - half_width, cx + half_width, cy + y, pixel);
+            if y != 0 {
+                self.fill_span_clipped(cx - half_width, cx + half_width, cy - y, pixel);
+            }
+        }
+    }
+    /// Fills a rectangle with its corners rounded off by quarter circles of `radius`, which is
+    /// limited to half the shorter side.
+    pub fn fill_round_rect(
+        &mut self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        radius: usize,
+        pixel: Pixel,
+    ) {
+        let radius = radius.min(w / 2).min(h / 2);
+        if radius == 0 {
+            self.fill_rect(x, y, w, h, pixel);
+            return;
+        }
+
+        self.fill_rect(x, y.saturating_add(radius), w, h - 2 * radius, pixel);
+        let corner = ellipse_half_widths(radius as i32, radius as i32, 0..radius as i64 + 1);
+        for (dy, half_width) in corner.into_iter().enumerate().skip(1) {
+            let inset = radius - half_width as usize;
+            let span = w - 2 * inset;
+            let top = y.saturating_add(radius - dy);
+            self.fill_rect(x.saturating_add(inset), top, span, 1, pixel);
+            let bottom = y.saturating_add(h - radius - 1 + dy);
+            self.fill_rect(x.saturating_add(inset), bottom, span, 1, pixel);
+        }
+    }
+    fn fill_span_clipped(&mut self, x0: i64, x1: i64, y: i64, pixel: Pixel) {
+        let x1 = x1.min(self.width as i64 - 1);
+        if y < 0 || y >= self.height as i64 || x1 < 0 || x0 > x1 {
+            return;
+        }
+
+        let x0 = x0.max(0) as usize;
+        let width = x1 as usize - x0 + 1;
+        self.fill_rect(x0, y as usize, width, 1, pixel);
+    }
+    /// Replaces the 4-connected region of pixels matching the one at `(x, y)` with `fill`.
+    pub fn flood_fill(&mut self, x: usize, y: usize, fill: Pixel) {
+        let target = self.pixels[self.index_or_panic(x, y)];
+        if target == fill {
+            return;
+        }
+
+        // Each entry is a seed inside a span of `target` pixels that still needs filling.
+        let mut seeds = vec![(x, y)];
+        while let Some((x, y)) = seeds.pop() {
+            let row = self.coord_to_index(0, y);
+            if self.pixels[row + x] != target {
+                continue;
+            }
+
+            let mut left = x;
+            while left > 0 && self.pixels[row + left - 1] == target {
+                left -= 1;
+            }
+            let mut right = x + 1;
+            while right < self.width && self.pixels[row + right] == target {
+                right += 1;
+            }
+            self.pixels[row + left..row + right].fill(fill);
+            self.mark_dirty(left, y, right - left, 1);
+
+            let above = y.checked_sub(1);
+            let below = Some(y + 1).filter(|y| *y < self.height);
+            for y in above.into_iter().chain(below) {
+                let row = self.coord_to_index(0, y);
+                let mut x = left;
+                while x < right {
+                    if self.pixels[row + x] != target {
+                        x += 1;
+                        continue;
+                    }
+                    seeds.push((x, y));
+                    while x < right && self.pixels[row + x] == target {
+                        x += 1;
+                    }
+                }
+            }
+        }
+    }
+    pub fn map_pixels(&mut self, mut f: impl FnMut(Pixel) -> Pixel) {
+        for pixel in self.pixels.iter_mut() {
+            *pixel = f(*pixel);
+        }
+        self.mark_all_dirty();
+    }
+    pub fn map_pixels_with_coords(&mut self, mut f: impl FnMut(usize, usize, Pixel) -> Pixel) {
+        let width = self.width;
+        for (row, pixels) in self.pixels.chunks_exact_mut(width.max(1)).enumerate() {
+            for (x, pixel) in pixels.iter_mut().enumerate() {
+                *pixel = f(x, row, *pixel);
+            }
+        }
+        self.mark_all_dirty();
+    }
+    /// Fills the buffer with a gradient from `top` in the first row to `bottom` in the last.
+    pub fn vertical_gradient(&mut self, top: Pixel, bottom: Pixel) {
+        let last_row = self.height.saturating_sub(1).max(1) as f32;
+        for y in 0..self.height {
+            let pixel = top.lerp(bottom, y as f32 / last_row);
+            self.row_mut(y).fill(pixel);
+        }
+    }
+    /// Tiles `cell`-sized squares of `a` and `b`, with `a` in the top left corner.
+    pub fn fill_checkerboard(&mut self, cell: usize, a: Pixel, b: Pixel) {
+        if cell == 0 {
+            return;
+        }
+        for y in 0..self.height {
+            let (even, odd) = match (y / cell) % 2 {
+                0 => (a, b),
+                _ => (b, a),
+            };
+            for (i, run) in self.row_mut(y).chunks_mut(cell).enumerate() {
+                run.fill(if i % 2 == 0 { even } else { odd });
+            }
+        }
+    }
+    pub fn grayscale(&mut self) {
+        self.map_pixels(Pixel::to_grayscale);
+    }
+    pub fn invert(&mut self) {
+        self.map_pixels(Pixel::inverted);
+    }
+    /// Averages every pixel with its neighbors up to `radius` away in both directions,
+    /// repeating the edge pixels past the borders.
+    pub fn box_blur(&mut self, radius: usize) {
+        if radius == 0 || self.pixels.is_empty() {
+            return;
+        }
+
+        // Both passes read from a copy so blurred pixels don't feed back into their neighbors.
+        let width = self.width;
+        let source = self.pixels.to_vec();
+        for (src, dst) in source
+            .chunks_exact(width)
+            .zip(self.pixels.chunks_exact_mut(width))
+        {
+            box_blur_run(src, dst, radius);
+        }
+        let mut column = Vec::with_capacity(self.height);
+        let mut blurred = vec![Pixel::black(); self.height];
+        for x in 0..width {
+            column.clear();
+            column.extend(self.pixels[x..].iter().step_by(width));
+            box_blur_run(&column, &mut blurred, radius);
+            for (pixel, out) in self.pixels[x..].iter_mut().step_by(width).zip(&blurred) {
+                *pixel = *out;
+            }
+        }
+        self.mark_all_dirty();
+    }
+    /// Approximates a Gaussian blur with standard deviation `sigma` by three box blurs.
+    pub fn gaussian_blur(&mut self, sigma: f32) {
+        for radius in gaussian_box_radii(sigma) {
+            self.box_blur(radius);
+        }
+    }
+    pub fn flip_horizontal(&mut self) {
+        for row in self.pixels.chunks_exact_mut(self.width.max(1)) {
+            row.reverse();
+        }
+        self.mark_all_dirty();
+    }
+    pub fn flip_vertical(&mut self) {
+        let width = self.width;
+        for y in 0..self.height / 2 {
+            let (top, bottom) = self.pixels.split_at_mut((self.height - 1 - y) * width);
+            top[y * width..(y + 1) * width].swap_with_slice(&mut bottom[..width]);
+        }
+        self.mark_all_dirty();
+    }
+    /// Returns a copy rotated a quarter turn clockwise.
+    pub fn rotate90(&self) -> FrameBuffer {
+        self.remapped(self.height, self.width, |x, y| (self.height - 1 - y, x))
+    }
+    pub fn rotate180(&self) -> FrameBuffer {
+        self.remapped(self.width, self.height, |x, y| {
+            (self.width - 1 - x, self.height - 1 - y)
+        })
+    }
+    /// Returns a copy rotated a quarter turn counterclockwise.
+    pub fn rotate270(&self) -> FrameBuffer {
+        self.remapped(self.height, self.width, |x, y| (y, self.width - 1 - x))
+    }
+    /// Copies every source pixel to the destination coordinate `map` returns for it.
+    fn remapped(
+        &self,
+        width: usize,
+        height: usize,
+        map: impl Fn(usize, usize) -> (usize, usize),
+    ) -> FrameBuffer {
+        let mut remapped = FrameBuffer::new(width, height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (dst_x, dst_y) = map(x, y);
+                remapped.pixels[dst_y * width + dst_x] = self.pixels[self.coord_to_index(x, y)];
+            }
+        }
+        remapped
+    }
+    pub fn fill_masked(&mut self, pixel: Pixel, mask: ChannelMask) {
+        for p in self.pixels.iter_mut() {
+            *p = mask.apply(*p, pixel);
+        }
+        self.mark_all_dirty();
+    }
+    /// Draws `text` with the built-in 8x8 font, its top left corner at `(x, y)`. Each `\n`
+    /// starts a new line; characters without a glyph are drawn as a box.
+    pub fn draw_text(&mut self, x: i32, y: i32, text: &str, color: Pixel) {
+        let mut y = y;
+        for line in text.lines() {
+            self.draw_text_line(x, y, line, color, 1);
+            y += GLYPH_HEIGHT as i32;
+        }
+    }
+    pub fn draw_text_centered(&mut self, cx: i32, cy: i32, text: &str, color: Pixel, scale: usize) {
+        let line_height = (GLYPH_HEIGHT * scale) as i32;
+        let line_count = text.lines().count() as i32;
+
+        let mut y = cy - line_height * line_count / 2;
+        for line in text.lines() {
+            let x = cx - (text::line_width(line) * scale) as i32 / 2;
+            self.draw_text_line(x, y, line, color, scale);
+            y += line_height;
+        }
+    }
+    fn draw_text_line(&mut self, x: i32, y: i32, line: &str, color: Pixel, scale: usize) {
+        let advance = (GLYPH_WIDTH * scale) as i32;
+        for (i, c) in line.chars().enumerate() {
+            self.draw_glyph(x + i as i32 * advance, y, text::glyph(c), color, scale);
+        }
+    }
+    fn draw_glyph(&mut self, x: i32, y: i32, glyph: Glyph, color: Pixel, scale: usize) {
+        for (row, bits) in glyph.into_iter().enumerate() {
+            for col in 0..GLYPH_WIDTH {
+                if bits >> col & 1 == 0 {
+                    continue;
+                }
+
+                let left = x + (col * scale) as i32;
+                let top = y + (row * scale) as i32;
+                for dy in 0..scale as i32 {
+                    for dx in 0..scale as i32 {
+                        self.set_pixel_clipped(
+                            left as i64 + dx as i64,
+                            top as i64 + dy as i64,
+                            color,
+                        );
+                    }
+                }
+            }
+        }
+    }
+    fn set_pixel_clipped(&mut self, x: i64, y: i64, pixel: Pixel) {
+        if let (Ok(x), Ok(y)) = (usize::try_from(x), usize::try_from(y)) {
+            if x < self.width && y < self.height {
+                self.set_pixel(x, y, pixel);
+            }
+        }
+    }
+
+    /// Copies `src` with its top left corner at `(dst_x, dst_y)`, clipping whatever falls
+    /// outside this buffer.
+    pub fn blit(&mut self, src: &FrameBuffer, dst_x: i32, dst_y: i32) {
+        self.blit_with(src, dst_x, dst_y, BlendMode::Replace);
+    }
+    /// Like [`FrameBuffer::blit`], but combines each source pixel with the one underneath it.
+    pub fn blit_with(&mut self, src: &FrameBuffer, dst_x: i32, dst_y: i32, mode: BlendMode) {
+        self.blit_rows(src, dst_x, dst_y, |src, dst| mode.blend_row(src, dst));
+    }
+    /// Like [`FrameBuffer::blit`], but leaves the destination alone wherever the source pixel
+    /// equals `key` exactly.
+    pub fn blit_color_key(&mut self, src: &FrameBuffer, dst_x: i32, dst_y: i32, key: Pixel) {
+        self.blit_rows(src, dst_x, dst_y, |src, dst| {
+            for (dst, src) in dst.iter_mut().zip(src) {
+                if *src != key {
+                    *dst = *src;
+                }
+            }
+        });
+    }
+    /// Calls `f` with every pair of source and destination rows that overlap once `src` is
+    /// placed at `(dst_x, dst_y)`, then marks the overlap dirty.
+    fn blit_rows(
+        &mut self,
+        src: &FrameBuffer,
+        dst_x: i32,
+        dst_y: i32,
+        mut f: impl FnMut(&[Pixel], &mut [Pixel]),
+    ) {
+        let clip = |dst: i32, src_len: usize, dst_len: usize| {
+            let skip = (-(dst as i64)).max(0) as usize;
+            let start = (dst as i64).max(0) as usize;
+            let len = src_len
+                .saturating_sub(skip)
+                .min(dst_len.saturating_sub(start));
+            (skip, start, len)
+        };
+        let (src_x, x, width) = clip(dst_x, src.width, self.width);
+        let (src_y, y, height) = clip(dst_y, src.height, self.height);
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        for row in 0..height {
+            let src_start = src.coord_to_index(src_x, src_y + row);
+            let dst_start = self.coord_to_index(x, y + row);
+            f(
+                &src.pixels[src_start..src_start + width],
+                &mut self.pixels[dst_start..dst_start + width],
+            );
+        }
+        self.mark_dirty(x, y, width, height);
+    }
+
+    pub fn resize_keep(
+        &mut self,
+        width: usize,
+        height: usize,
+        fill: Pixel,
+    ) -> Result<(), SizeOverflow> {
+        let mut resized = FrameBuffer::try_new(width, height)?;
+        resized.pixels.fill(fill);
+
+        let copy_width = self.width.min(width);
+        for y in 0..self.height.min(height) {
+            let src = self.coord_to_index(0, y);
+            let dst = resized.coord_to_index(0, y);
+            resized.pixels[dst..dst + copy_width]
+                .copy_from_slice(&self.pixels[src..src + copy_width]);
+        }
+        *self = resized;
+        Ok(())
+    }
+    /// Scales the current content to the new size, with bilinear filtering if `smooth` is set.
+    /// On a [`crate::Pixely`], use [`crate::Pixely::resize_framebuffer_scaled`] instead, so the
+    /// texture is recreated at the new size.
+    pub fn resize_scaled(
+        &mut self,
+        width: usize,
+        height: usize,
+        smooth: bool,
+    ) -> Result<(), SizeOverflow> {
+        pixel_count(width, height)?;
+        *self = if smooth {
+            self.scaled_bilinear(width, height)
+        } else {
+            self.scaled_nearest(width, height)
+        };
+        Ok(())
+    }
+    /// A copy resampled to `width` by `height` with nearest-neighbor sampling.
+    pub fn scaled_nearest(&self, width: usize, height: usize) -> FrameBuffer {
+        let mut scaled = FrameBuffer::new(width, height);
+        if self.pixels.is_empty() {
+            return scaled;
+        }
+
+        for y in 0..height {
+            let src_y = y * self.height / height;
+            for x in 0..width {
+                let src_x = x * self.width / width;
+                scaled.pixels[y * width + x] = self.pixels[self.coord_to_index(src_x, src_y)];
+            }
         }
+        scaled
     }
+    /// A copy resampled to `width` by `height` with bilinear filtering.
+    pub fn scaled_bilinear(&self, width: usize, height: usize) -> FrameBuffer {
+        let mut scaled = FrameBuffer::new(width, height);
+        if self.pixels.is_empty() {
+            return scaled;
+        }
+
+        let x_ratio = self.width as f32 / width as f32;
+        let y_ratio = self.height as f32 / height as f32;
+        let max_x = (self.width - 1) as f32;
+        let max_y = (self.height - 1) as f32;
+        for y in 0..height {
+            let src_y = ((y as f32 + 0.5) * y_ratio - 0.5).clamp(0.0, max_y);
+            let y0 = src_y as usize;
+            let y1 = (y0 + 1).min(self.height - 1);
+            let ty = src_y - y0 as f32;
+            for x in 0..width {
+                let src_x = ((x as f32 + 0.5) * x_ratio - 0.5).clamp(0.0, max_x);
+                let x0 = src_x as usize;
+                let x1 = (x0 + 1).min(self.width - 1);
+                let tx = src_x - x0 as f32;
+
+                let top = self.pixels[self.coord_to_index(x0, y0)]
+                    .lerp(self.pixels[self.coord_to_index(x1, y0)], tx);
+                let bottom = self.pixels[self.coord_to_index(x0, y1)]
+                    .lerp(self.pixels[self.coord_to_index(x1, y1)], tx);
+                scaled.pixels[y * width + x] = top.lerp(bottom, ty);
+            }
+        }
+        scaled
+    }
+
+    /// Moves each color channel by its own offset, sampling clamped to the edges. Alpha stays put.
+    pub fn shift_channels(
+        &mut self,
+        r_off: (isize, isize),
+        g_off: (isize, isize),
+        b_off: (isize, isize),
+    ) {
+        if self.pixels.is_empty() {
+            return;
+        }
+
+        let source = self.pixels.clone();
+        let sample = |x: usize, y: usize, (dx, dy): (isize, isize)| {
+            let src_x = (x as isize - dx).clamp(0, self.width as isize - 1) as usize;
+            let src_y = (y as isize - dy).clamp(0, self.height as isize - 1) as usize;
+            source[self.coord_to_index(src_x, src_y)]
+        };
+
+        let mut shifted = source.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pixel = &mut shifted[y * self.width + x];
+                pixel.red = sample(x, y, r_off).red;
+                pixel.green = sample(x, y, g_off).green;
+                pixel.blue = sample(x, y, b_off).blue;
+            }
+        }
+        self.pixels = shifted;
+        self.mark_all_dirty();
+    }
+
+    /// Compares two buffers pixel by pixel, or returns `None` if they're identical. Where the
+    /// sizes differ, pixels only one of them covers count as differing. For the largest channel
+    /// difference and a heatmap of where the buffers differ, see [`FrameBuffer::diff_heatmap`].
+    pub fn diff(&self, other: &Self) -> Option<FrameDiff> {
+        let width = self.width.max(other.width);
+        let height = self.height.max(other.height);
+        let mut differing = 0;
+        let mut bounds: Option<DirtyRegion> = None;
+        for y in 0..height {
+            for x in 0..width {
+                if self.get_pixel_checked(x, y) != other.get_pixel_checked(x, y) {
+                    differing += 1;
+                    let pixel = DirtyRegion::point(x, y);
+                    bounds = Some(bounds.map_or(pixel, |bounds| bounds.union(pixel)));
+                }
+            }
+        }
+
+        bounds.map(|bounds| FrameDiff {
+            differing,
+            bounds: bounds.rect(),
+        })
+    }
+    /// Like [`FrameBuffer::diff`] for buffers of the same size, with the largest channel
+    /// difference and a heatmap that's black where the buffers agree and red where they
+    /// differ, brighter the larger the difference.
+    pub fn diff_heatmap(&self, other: &Self) -> Result<DiffResult, DimensionMismatch> {
+        if self.width != other.width || self.height != other.height {
+            return Err(DimensionMismatch {
+                expected: (self.width, self.height),
+                found: (other.width, other.height),
+            });
+        }
+
+        let mut heatmap = FrameBuffer::new(self.width, self.height);
+        let mut differing = 0;
+        let mut max_channel_delta = 0;
+        let mut bounds: Option<DirtyRegion> = None;
+        for (i, (a, b)) in self.pixels.iter().zip(other.pixels.iter()).enumerate() {
+            if a == b {
+                continue;
+            }
+
+            differing += 1;
+            let pixel = DirtyRegion::point(i % self.width, i / self.width);
+            bounds = Some(bounds.map_or(pixel, |bounds| bounds.union(pixel)));
+            max_channel_delta = max_channel_delta
+                .max(a.red.abs_diff(b.red))
+                .max(a.green.abs_diff(b.green))
+                .max(a.blue.abs_diff(b.blue))
+                .max(a.alpha.abs_diff(b.alpha));
+            // The largest possible distance across all four channels is sqrt(4 * 255^2) = 510.
+            let magnitude = a.distance_rgba(b) / 510.0;
+            heatmap.pixels[i].red = (magnitude * 255.0).ceil().min(255.0) as u8;
+        }
+
+        Ok(DiffResult {
+            differing,
+            max_channel_delta,
+            bounds: bounds.map(DirtyRegion::rect),
+            heatmap,
+        })
+    }
+
+    /// The bounding box of all pixels written since the last [`FrameBuffer::clear_dirty`],
+    /// as `(x, y, width, height)`.
+    pub fn dirty_rect(&self) -> Option<(usize, usize, usize, usize)> {
+        self.dirty.map(DirtyRegion::rect)
+    }
+    /// The rows touched since the last [`FrameBuffer::clear_dirty`], top to bottom.
+    pub fn dirty_rows(&self) -> impl Iterator<Item = (usize, &[Pixel])> {
+        self.dirty_rows
+            .iter()
+            .enumerate()
+            .filter(|(_, dirty)| **dirty)
+            .map(|(y, _)| (y, &self.pixels[y * self.width..(y + 1) * self.width]))
+    }
+    pub fn mark_all_dirty(&mut self) {
+        self.dirty = None;
+        self.mark_dirty(0, 0, self.width, self.height);
+    }
+    pub fn clear_dirty(&mut self) {
+        self.dirty = None;
+        self.dirty_rows.fill(false);
+    }
+    pub(crate) fn mark_dirty(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let region = DirtyRegion {
+            min_x: x,
+            min_y: y,
+            max_x: x + width,
+            max_y: y + height,
+        };
+        self.dirty = Some(match self.dirty {
+            Some(dirty) => dirty.union(region),
+            None => region,
+        });
+        self.dirty_rows[y..y + height].fill(true);
+    }
+
     fn coord_to_index(&self, x: usize, y: usize) -> usize {
         y * self.width + x
     }
+    fn bounds_check(&self, x: usize, y: usize) -> Result<usize, OutOfBounds> {
+        if x < self.width && y < self.height {
+            Ok(self.coord_to_index(x, y))
+        } else {
+            Err(OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            })
+        }
+    }
+    fn checked_index(&self, x: usize, y: usize) -> Option<usize> {
+        self.bounds_check(x, y).ok()
+    }
+    fn index_or_panic(&self, x: usize, y: usize) -> usize {
+        self.bounds_check(x, y).unwrap_or_else(|e| panic!("{e}"))
+    }
 
     pub fn width(&self) -> usize {
         self.width
@@ -32,13 +806,83 @@ impl FrameBuffer {
     pub fn height(&self) -> usize {
         self.height
     }
+    /// All pixels in row-major order.
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+    /// All pixels in row-major order, marking the whole buffer dirty. The slice can't be
+    /// resized; use [`FrameBuffer::resize_keep`] to change the dimensions.
+    pub fn pixels_mut(&mut self) -> &mut [Pixel] {
+        self.mark_all_dirty();
+        &mut self.pixels
+    }
+    /// Every pixel as `(x, y, pixel)`, in row-major order.
+    pub fn enumerate_pixels(&self) -> impl Iterator<Item = (usize, usize, Pixel)> + '_ {
+        let width = self.width;
+        self.pixels
+            .iter()
+            .enumerate()
+            .map(move |(i, pixel)| (i % width, i / width, *pixel))
+    }
+    /// Like [`FrameBuffer::enumerate_pixels`], but mutable, marking the whole buffer dirty.
+    pub fn enumerate_pixels_mut(
+        &mut self,
+    ) -> impl Iterator<Item = (usize, usize, &mut Pixel)> + '_ {
+        let width = self.width;
+        self.pixels_mut()
+            .iter_mut()
+            .enumerate()
+            .map(move |(i, pixel)| (i % width, i / width, pixel))
+    }
     pub fn as_bytes(&self) -> &[u8] {
         cast_slice(&self.pixels)
     }
+    /// Every pixel packed as by [`Pixel::to_u32`].
+    pub fn as_u32_slice(&self) -> &[u32] {
+        cast_slice(&self.pixels)
+    }
+    pub fn as_u32_slice_mut(&mut self) -> &mut [u32] {
+        self.mark_all_dirty();
+        cast_slice_mut(&mut self.pixels)
+    }
+    /// A view of the region at `(x, y)` of size `w` by `h`, clipped to the buffer.
+    pub fn view(&self, x: usize, y: usize, w: usize, h: usize) -> FrameBufferView<'_> {
+        FrameBufferView::new(self, x, y, w, h)
+    }
+    /// A mutable view of the region at `(x, y)` of size `w` by `h`, clipped to the buffer.
+    pub fn view_mut(&mut self, x: usize, y: usize, w: usize, h: usize) -> FrameBufferViewMut<'_> {
+        FrameBufferViewMut::new(self, x, y, w, h)
+    }
+    pub fn row(&self, y: usize) -> &[Pixel] {
+        let range = self.row_range(y);
+        &self.pixels[range]
+    }
+    pub fn row_mut(&mut self, y: usize) -> &mut [Pixel] {
+        let range = self.row_range(y);
+        self.mark_dirty(0, y, self.width, 1);
+        &mut self.pixels[range]
+    }
+    pub fn rows(&self) -> impl Iterator<Item = &[Pixel]> {
+        (0..self.height).map(|y| self.row(y))
+    }
+    pub fn rows_mut(&mut self) -> impl Iterator<Item = &mut [Pixel]> {
+        self.mark_all_dirty();
+        // `max` keeps zero-width buffers from panicking; they have no pixels to split anyway.
+        self.pixels.chunks_exact_mut(self.width.max(1))
+    }
+    fn row_range(&self, y: usize) -> Range<usize> {
+        assert!(
+            y < self.height,
+            "row {y} is out of bounds for a framebuffer of height {}",
+            self.height
+        );
+        y * self.width..(y + 1) * self.width
+    }
 }
 
+/// Aligned like a `u32`, so the framebuffer can be viewed as packed `u32`s.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
-#[repr(C)]
+#[repr(C, align(4))]
 pub struct Pixel {
     pub red: u8,
     pub green: u8,
@@ -46,14 +890,1076 @@ pub struct Pixel {
     pub alpha: u8,
 }
 impl Pixel {
-    pub fn black() -> Pixel {
+    pub const BLACK: Pixel = Pixel::rgb(0, 0, 0);
+    pub const WHITE: Pixel = Pixel::rgb(255, 255, 255);
+    pub const RED: Pixel = Pixel::rgb(255, 0, 0);
+    pub const GREEN: Pixel = Pixel::rgb(0, 255, 0);
+    pub const BLUE: Pixel = Pixel::rgb(0, 0, 255);
+    pub const YELLOW: Pixel = Pixel::rgb(255, 255, 0);
+    pub const CYAN: Pixel = Pixel::rgb(0, 255, 255);
+    pub const MAGENTA: Pixel = Pixel::rgb(255, 0, 255);
+    pub const TRANSPARENT: Pixel = Pixel::rgba(0, 0, 0, 0);
+
+    pub const fn rgb(red: u8, green: u8, blue: u8) -> Pixel {
+        Pixel::rgba(red, green, blue, 255)
+    }
+    pub const fn rgba(red: u8, green: u8, blue: u8, alpha: u8) -> Pixel {
+        Pixel {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+    pub const fn black() -> Pixel {
+        Pixel::BLACK
+    }
+    pub const fn white() -> Pixel {
+        Pixel::WHITE
+    }
+
+    /// Reads `0xRRGGBB`, with an alpha of 255.
+    pub const fn from_hex_u32(hex: u32) -> Pixel {
+        Pixel::from_hex_rgba_u32(hex << 8 | 0xFF)
+    }
+    /// Reads `0xRRGGBBAA`.
+    pub const fn from_hex_rgba_u32(hex: u32) -> Pixel {
+        let [red, green, blue, alpha] = hex.to_be_bytes();
+        Pixel::rgba(red, green, blue, alpha)
+    }
+    /// Parses `#rgb`, `#rrggbb` or `#rrggbbaa`; the leading `#` is optional.
+    pub fn from_hex_str(s: &str) -> Result<Pixel, ParseHexError> {
+        let digits = s.strip_prefix('#').unwrap_or(s);
+        if let Some(c) = digits.chars().find(|c| !c.is_ascii_hexdigit()) {
+            return Err(ParseHexError::InvalidDigit(c));
+        }
+
+        let value = |range: Range<usize>| u8::from_str_radix(&digits[range], 16).unwrap();
+        match digits.len() {
+            3 => {
+                let [red, green, blue] = [0, 1, 2].map(|i| value(i..i + 1) * 0x11);
+                Ok(Pixel::rgb(red, green, blue))
+            }
+            6 => Ok(Pixel::rgb(value(0..2), value(2..4), value(4..6))),
+            8 => Ok(Pixel::rgba(
+                value(0..2),
+                value(2..4),
+                value(4..6),
+                value(6..8),
+            )),
+            len => Err(ParseHexError::InvalidLength(len)),
+        }
+    }
+
+    /// Composites this pixel over `background` with straight (non-premultiplied) alpha.
+    pub fn over(self, background: Pixel) -> Pixel {
+        match self.alpha {
+            255 => return self,
+            0 => return background,
+            _ => {}
+        }
+
+        let src_alpha = self.alpha as f32 / 255.0;
+        let dst_alpha = background.alpha as f32 / 255.0 * (1.0 - src_alpha);
+        let out_alpha = src_alpha + dst_alpha;
+        let channel = |src: u8, dst: u8| {
+            ((src as f32 * src_alpha + dst as f32 * dst_alpha) / out_alpha).round() as u8
+        };
+
         Pixel {
-            red: 0,
-            green: 0,
-            blue: 0,
-            alpha: 255,
+            red: channel(self.red, background.red),
+            green: channel(self.green, background.green),
+            blue: channel(self.blue, background.blue),
+            alpha: (out_alpha * 255.0).round() as u8,
+        }
+    }
+
+    /// Composites this pixel over `background`, both with premultiplied alpha.
+    pub fn over_premultiplied(self, background: Pixel) -> Pixel {
+        let inverse_alpha = 255 - self.alpha as u16;
+        let channel = |src: u8, dst: u8| {
+            let dst = (dst as u16 * inverse_alpha + 127) / 255;
+            (src as u16 + dst).min(255) as u8
+        };
+
+        Pixel {
+            red: channel(self.red, background.red),
+            green: channel(self.green, background.green),
+            blue: channel(self.blue, background.blue),
+            alpha: channel(self.alpha, background.alpha),
+        }
+    }
+    /// Multiplies the color channels by alpha, for use with premultiplied blending.
+    pub fn premultiplied(self) -> Pixel {
+        let alpha = self.alpha as u16;
+        let channel = |c: u8| ((c as u16 * alpha + 127) / 255) as u8;
+        Pixel::rgba(
+            channel(self.red),
+            channel(self.green),
+            channel(self.blue),
+            self.alpha,
+        )
+    }
+
+    /// Interpolates every channel, including alpha, from `self` at `t = 0.0` to `other` at
+    /// `t = 1.0`. `t` is clamped to that range.
+    pub fn lerp(self, other: Pixel, t: f32) -> Pixel {
+        let t = t.clamp(0.0, 1.0);
+        let lerp = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Pixel {
+            red: lerp(self.red, other.red),
+            green: lerp(self.green, other.green),
+            blue: lerp(self.blue, other.blue),
+            alpha: lerp(self.alpha, other.alpha),
+        }
+    }
+    /// Converts to gray using the Rec. 601 luma weights, keeping alpha.
+    pub fn to_grayscale(self) -> Pixel {
+        let weighted = 299 * self.red as u32 + 587 * self.green as u32 + 114 * self.blue as u32;
+        let luma = ((weighted + 500) / 1000) as u8;
+        Pixel::rgba(luma, luma, luma, self.alpha)
+    }
+    /// Inverts the color channels, keeping alpha.
+    pub fn inverted(self) -> Pixel {
+        Pixel::rgba(
+            255 - self.red,
+            255 - self.green,
+            255 - self.blue,
+            self.alpha,
+        )
+    }
+
+    /// Packs the pixel as `0xRRGGBBAA`, independent of endianness.
+    pub const fn to_rgba_u32(self) -> u32 {
+        u32::from_be_bytes([self.red, self.green, self.blue, self.alpha])
+    }
+    pub const fn from_rgba_u32(value: u32) -> Pixel {
+        Pixel::from_hex_rgba_u32(value)
+    }
+    /// Packs the pixel as `0xAARRGGBB`, the layout used by softbuffer and minifb.
+    pub const fn to_argb_u32(self) -> u32 {
+        u32::from_be_bytes([self.alpha, self.red, self.green, self.blue])
+    }
+    pub const fn from_argb_u32(value: u32) -> Pixel {
+        let [alpha, red, green, blue] = value.to_be_bytes();
+        Pixel::rgba(red, green, blue, alpha)
+    }
+    /// Packs the pixel in memory order, so the bytes of the result are red, green, blue, alpha
+    /// in native endianness. On little-endian targets this reads as `0xAABBGGRR`.
+    pub fn to_u32(self) -> u32 {
+        u32::from_ne_bytes([self.red, self.green, self.blue, self.alpha])
+    }
+    pub fn from_u32(value: u32) -> Pixel {
+        let [red, green, blue, alpha] = value.to_ne_bytes();
+        Pixel {
+            red,
+            green,
+            blue,
+            alpha,
+        }
+    }
+
+    /// The squared euclidean distance between the RGB channels; alpha is ignored.
+    pub fn distance_squared(&self, other: &Pixel) -> u32 {
+        channel_delta_squared(self.red, other.red)
+            + channel_delta_squared(self.green, other.green)
+            + channel_delta_squared(self.blue, other.blue)
+    }
+    pub fn distance(&self, other: &Pixel) -> f32 {
+        (self.distance_squared(other) as f32).sqrt()
+    }
+    /// Like [`Pixel::distance_squared`], but with alpha as a fourth channel.
+    pub fn distance_squared_rgba(&self, other: &Pixel) -> u32 {
+        self.distance_squared(other) + channel_delta_squared(self.alpha, other.alpha)
+    }
+    pub fn distance_rgba(&self, other: &Pixel) -> f32 {
+        (self.distance_squared_rgba(other) as f32).sqrt()
+    }
+}
+/// Copies tightly packed RGBA8 data, which unlike `[Pixel]` needn't be aligned.
+fn pixels_from_bytes(bytes: &[u8]) -> Box<[Pixel]> {
+    bytes
+        .chunks_exact(4)
+        .map(|c| Pixel::rgba(c[0], c[1], c[2], c[3]))
+        .collect()
+}
+fn channel_delta_squared(a: u8, b: u8) -> u32 {
+    let delta = a.abs_diff(b) as u32;
+    delta * delta
+}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for Pixel {}
+#[cfg(not(feature = "bytemuck"))]
+unsafe impl crate::cast::Plain for Pixel {}
+/// The points of a circle's first octant, from `(radius, 0)` to the diagonal.
+fn circle_octant(radius: i32) -> Vec<(i32, i32)> {
+    let mut points = Vec::new();
+    if radius < 0 {
+        return points;
+    }
+
+    let (mut x, mut y) = (radius, 0);
+    let mut error = 1 - radius;
+    while x >= y {
+        points.push((x, y));
+        y += 1;
+        if error < 0 {
+            error += 2 * y + 1;
+        } else {
+            x -= 1;
+            error += 2 * (y - x) + 1;
+        }
+    }
+    points
+}
+/// How far the ellipse with radii `rx` and `ry` reaches to either side of its center on each
+/// row in `rows`, counted from the center down, found with the midpoint ellipse algorithm.
+fn ellipse_half_widths(rx: i32, ry: i32, rows: Range<i64>) -> Vec<i32> {
+    let rows = rows.start.max(0)..rows.end.min(ry as i64 + 1);
+    if rx < 0 || ry < 0 || rows.is_empty() {
+        return Vec::new();
+    }
+    let mut half_widths = vec![rx; (rows.end - rows.start) as usize];
+    if rx == 0 || ry == 0 {
+        return half_widths;
+    }
+
+    let mut set = |y: i128, x: i128| {
+        if let Some(half_width) = half_widths.get_mut((y as i64 - rows.start) as usize) {
+            *half_width = x as i32;
+        }
+    };
+    // Large radii overflow the squared and scaled terms in `i64`.
+    let (rx2, ry2) = (rx as i128 * rx as i128, ry as i128 * ry as i128);
+    let (mut x, mut y) = (0i128, ry as i128);
+    let (mut dx, mut dy) = (0, 2 * rx2 * y);
+
+    // The decision variables are scaled by 4 to keep the half pixel offsets whole.
+    let mut error = 4 * ry2 - 4 * rx2 * y + rx2;
+    while dx < dy {
+        set(y, x);
+        x += 1;
+        dx += 2 * ry2;
+        if error < 0 {
+            error += 4 * (dx + ry2);
+        } else {
+            y -= 1;
+            dy -= 2 * rx2;
+            error += 4 * (dx - dy + ry2);
+        }
+    }
+
+    let mut error = ry2 * (2 * x + 1) * (2 * x + 1) + 4 * rx2 * (y - 1) * (y - 1) - 4 * rx2 * ry2;
+    // Rows are only visited from the top down, so stop once they're above the wanted range.
+    while y >= rows.start as i128 {
+        set(y, x);
+        y -= 1;
+        dy -= 2 * rx2;
+        if error > 0 {
+            error += 4 * (rx2 - dy);
+        } else {
+            x += 1;
+            dx += 2 * ry2;
+            error += 4 * (dx - dy + rx2);
+        }
+    }
+    half_widths
+}
+/// Writes the average of each `2 * radius + 1` wide window of `src` to `dst`, clamping
+/// indices to the ends of `src`.
+fn box_blur_run(src: &[Pixel], dst: &mut [Pixel], radius: usize) {
+    let last = src.len() - 1;
+    let channels = |p: Pixel| [p.red, p.green, p.blue, p.alpha].map(u64::from);
+    let mut sum = [0u64; 4];
+    let add = |sum: &mut [u64; 4], i: usize, sign: i64| {
+        for (s, c) in sum.iter_mut().zip(channels(src[i.min(last)])) {
+            *s = s.wrapping_add_signed(c as i64 * sign);
+        }
+    };
+
+    // The window starting at index 0 covers `radius` copies of the first pixel on the left.
+    for _ in 0..radius {
+        add(&mut sum, 0, 1);
+    }
+    for i in 0..=radius {
+        add(&mut sum, i, 1);
+    }
+
+    let count = 2 * radius as u64 + 1;
+    for (i, out) in dst.iter_mut().enumerate() {
+        let [red, green, blue, alpha] = sum.map(|s| ((s + count / 2) / count) as u8);
+        *out = Pixel::rgba(red, green, blue, alpha);
+        add(&mut sum, i + radius + 1, 1);
+        add(&mut sum, i.saturating_sub(radius), -1);
+    }
+}
+/// The radii of three box blurs that together approximate a Gaussian with `sigma`.
+fn gaussian_box_radii(sigma: f32) -> [usize; 3] {
+    if !sigma.is_finite() || sigma <= 0.0 {
+        return [0; 3];
+    }
+
+    // See Kovesi, "Fast Almost-Gaussian Filtering".
+    let variance = 12.0 * sigma * sigma;
+    let ideal_width = (variance / 3.0 + 1.0).sqrt();
+    let mut lower = ideal_width.floor() as i64;
+    if lower % 2 == 0 {
+        lower -= 1;
+    }
+    let lower_f = lower as f32;
+    let lower_count = ((variance - 3.0 * lower_f * lower_f - 12.0 * lower_f - 9.0)
+        / (-4.0 * lower_f - 4.0))
+        .round() as i64;
+    let width = |i: i64| if i < lower_count { lower } else { lower + 2 };
+    [0, 1, 2].map(|i| (width(i).max(1) as usize - 1) / 2)
+}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for Pixel {}
+impl Default for Pixel {
+    fn default() -> Self {
+        Pixel::black()
+    }
+}
+/// Channels in `[red, green, blue, alpha]` order.
+impl From<[u8; 4]> for Pixel {
+    fn from([red, green, blue, alpha]: [u8; 4]) -> Self {
+        Pixel::rgba(red, green, blue, alpha)
+    }
+}
+/// Channels in `[red, green, blue, alpha]` order.
+impl From<Pixel> for [u8; 4] {
+    fn from(pixel: Pixel) -> Self {
+        [pixel.red, pixel.green, pixel.blue, pixel.alpha]
+    }
+}
+/// Channels in `(red, green, blue, alpha)` order.
+impl From<(u8, u8, u8, u8)> for Pixel {
+    fn from((red, green, blue, alpha): (u8, u8, u8, u8)) -> Self {
+        Pixel::rgba(red, green, blue, alpha)
+    }
+}
+/// Channels in `(red, green, blue, alpha)` order.
+impl From<Pixel> for (u8, u8, u8, u8) {
+    fn from(pixel: Pixel) -> Self {
+        (pixel.red, pixel.green, pixel.blue, pixel.alpha)
+    }
+}
+/// Formats as `#rrggbbaa`.
+impl fmt::Display for Pixel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:08x}", self.to_rgba_u32())
+    }
+}
+
+#[cfg(feature = "png")]
+impl FrameBuffer {
+    pub fn save_png(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.as_bytes())?;
+        writer.finish()?;
+        Ok(())
+    }
+    /// Writes the heatmap of [`FrameBuffer::diff_heatmap`], failing with
+    /// [`io::ErrorKind::InvalidInput`] if the sizes differ.
+    pub fn save_diff_png(&self, other: &Self, path: impl AsRef<Path>) -> io::Result<()> {
+        let diff = self
+            .diff_heatmap(other)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        diff.heatmap.save_png(path)
+    }
+}
+
+#[cfg(feature = "image")]
+impl FrameBuffer {
+    /// Decodes any format supported by the `image` crate, converting it to RGBA8.
+    pub fn from_image_path(path: impl AsRef<Path>) -> Result<FrameBuffer, image::ImageError> {
+        let image = image::open(path)?.into_rgba8();
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        Ok(FrameBuffer::from_boxed(
+            width,
+            height,
+            pixels_from_bytes(image.as_raw()),
+        ))
+    }
+}
+
+#[cfg(feature = "tiny-skia")]
+impl FrameBuffer {
+    /// Copies a pixmap, converting its premultiplied colors to the straight alpha pixely uses.
+    pub fn from_tiny_skia_pixmap(pixmap: &tiny_skia::Pixmap) -> FrameBuffer {
+        let pixels = pixmap
+            .pixels()
+            .iter()
+            .map(|pixel| {
+                let color = pixel.demultiply();
+                Pixel::rgba(color.red(), color.green(), color.blue(), color.alpha())
+            })
+            .collect();
+        Self::from_boxed(pixmap.width() as usize, pixmap.height() as usize, pixels)
+    }
+    /// Overwrites a pixmap of the same size with this buffer, premultiplying the colors.
+    pub fn copy_to_tiny_skia(
+        &self,
+        pixmap: &mut tiny_skia::Pixmap,
+    ) -> Result<(), DimensionMismatch> {
+        let found = (pixmap.width() as usize, pixmap.height() as usize);
+        if found != (self.width, self.height) {
+            return Err(DimensionMismatch {
+                expected: (self.width, self.height),
+                found,
+            });
+        }
+
+        for (dst, src) in pixmap.pixels_mut().iter_mut().zip(self.pixels.iter()) {
+            *dst = tiny_skia::ColorU8::from_rgba(src.red, src.green, src.blue, src.alpha)
+                .premultiply();
+        }
+        Ok(())
+    }
+}
+
+impl Canvas for FrameBuffer {
+    fn width(&self) -> usize {
+        self.width
+    }
+    fn height(&self) -> usize {
+        self.height
+    }
+    fn get_pixel_checked(&self, x: usize, y: usize) -> Option<Pixel> {
+        FrameBuffer::get_pixel_checked(self, x, y)
+    }
+    fn set_pixel_checked(&mut self, x: usize, y: usize, pixel: Pixel) -> Result<(), OutOfBounds> {
+        FrameBuffer::set_pixel_checked(self, x, y, pixel)
+    }
+    fn fill_row(&mut self, y: usize, xs: Range<usize>, pixel: Pixel) {
+        let start = self.coord_to_index(0, y);
+        self.pixels[start + xs.start..start + xs.end].fill(pixel);
+        self.mark_dirty(xs.start, y, xs.len(), 1);
+    }
+}
+
+/// Compares dimensions and pixels; what's dirty doesn't matter.
+impl PartialEq for FrameBuffer {
+    fn eq(&self, other: &Self) -> bool {
+        self.width == other.width && self.height == other.height && self.pixels == other.pixels
+    }
+}
+impl Eq for FrameBuffer {}
+/// Prints the dimensions only, not the pixel data.
+impl fmt::Debug for FrameBuffer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("FrameBuffer")
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Index<(usize, usize)> for FrameBuffer {
+    type Output = Pixel;
+
+    fn index(&self, (x, y): (usize, usize)) -> &Pixel {
+        &self.pixels[self.index_or_panic(x, y)]
+    }
+}
+impl IndexMut<(usize, usize)> for FrameBuffer {
+    fn index_mut(&mut self, (x, y): (usize, usize)) -> &mut Pixel {
+        let i = self.index_or_panic(x, y);
+        self.mark_dirty(x, y, 1, 1);
+        &mut self.pixels[i]
+    }
+}
+
+/// The number of pixels in a `width` by `height` buffer, if its size in bytes fits in a `usize`.
+fn pixel_count(width: usize, height: usize) -> Result<usize, SizeOverflow> {
+    width
+        .checked_mul(height)
+        .filter(|len| len.checked_mul(size_of::<Pixel>()).is_some())
+        .ok_or(SizeOverflow { width, height })
+}
+fn check_len(expected: usize, found: usize) -> Result<(), SizeMismatch> {
+    match expected == found {
+        true => Ok(()),
+        false => Err(SizeMismatch { expected, found }),
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SizeMismatch {
+    pub expected: usize,
+    pub found: usize,
+}
+impl fmt::Display for SizeMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} elements of pixel data, found {}",
+            self.expected, self.found
+        )
+    }
+}
+impl Error for SizeMismatch {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct SizeOverflow {
+    pub width: usize,
+    pub height: usize,
+}
+impl fmt::Display for SizeOverflow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "a framebuffer of {}x{} pixels is too large to allocate",
+            self.width, self.height
+        )
+    }
+}
+impl Error for SizeOverflow {}
+
+/// Why [`FrameBuffer::from_pixels`] or [`FrameBuffer::from_bytes`] rejected its input.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SizeError {
+    Overflow(SizeOverflow),
+    Mismatch(SizeMismatch),
+}
+impl fmt::Display for SizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overflow(e) => write!(f, "{e}"),
+            Self::Mismatch(e) => write!(f, "{e}"),
+        }
+    }
+}
+impl Error for SizeError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Overflow(e) => Some(e),
+            Self::Mismatch(e) => Some(e),
+        }
+    }
+}
+impl From<SizeOverflow> for SizeError {
+    fn from(e: SizeOverflow) -> Self {
+        Self::Overflow(e)
+    }
+}
+impl From<SizeMismatch> for SizeError {
+    fn from(e: SizeMismatch) -> Self {
+        Self::Mismatch(e)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutOfBounds {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+impl fmt::Display for OutOfBounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "pixel ({}, {}) is out of bounds for a {}x{} framebuffer",
+            self.x, self.y, self.width, self.height
+        )
+    }
+}
+impl Error for OutOfBounds {}
+
+/// How two buffers compared with [`FrameBuffer::diff`] differ.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FrameDiff {
+    pub differing: usize,
+    /// The bounding box of the differing pixels as `(x, y, width, height)`.
+    pub bounds: (usize, usize, usize, usize),
+}
+
+/// The detailed comparison from [`FrameBuffer::diff_heatmap`].
+pub struct DiffResult {
+    pub differing: usize,
+    pub max_channel_delta: u8,
+    /// The bounding box of the differing pixels as `(x, y, width, height)`, or `None` if the
+    /// buffers are identical.
+    pub bounds: Option<(usize, usize, usize, usize)>,
+    pub heatmap: FrameBuffer,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct DimensionMismatch {
+    pub expected: (usize, usize),
+    pub found: (usize, usize),
+}
+impl fmt::Display for DimensionMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected a {}x{} framebuffer, found {}x{}",
+            self.expected.0, self.expected.1, self.found.0, self.found.1
+        )
+    }
+}
+impl Error for DimensionMismatch {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ParseHexError {
+    InvalidLength(usize),
+    InvalidDigit(char),
+}
+impl fmt::Display for ParseHexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidLength(len) => {
+                write!(f, "expected 3, 6 or 8 hex digits, found {len}")
+            }
+            Self::InvalidDigit(c) => write!(f, "{c:?} is not a hex digit"),
+        }
+    }
+}
+impl Error for ParseHexError {}
+
+/// `(x, y, width, height)`
+type Rect = (usize, usize, usize, usize);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct DirtyRegion {
+    min_x: usize,
+    min_y: usize,
+    max_x: usize,
+    max_y: usize,
+}
+impl DirtyRegion {
+    fn point(x: usize, y: usize) -> DirtyRegion {
+        DirtyRegion {
+            min_x: x,
+            min_y: y,
+            max_x: x + 1,
+            max_y: y + 1,
+        }
+    }
+    fn rect(self) -> Rect {
+        let DirtyRegion {
+            min_x,
+            min_y,
+            max_x,
+            max_y,
+        } = self;
+        (min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+    fn union(self, other: DirtyRegion) -> DirtyRegion {
+        DirtyRegion {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ChannelMask {
+    pub r: bool,
+    pub g: bool,
+    pub b: bool,
+    pub a: bool,
+}
+impl ChannelMask {
+    pub const ALL: ChannelMask = ChannelMask::new(true, true, true, true);
+    pub const RGB: ChannelMask = ChannelMask::new(true, true, true, false);
+    pub const ALPHA: ChannelMask = ChannelMask::new(false, false, false, true);
+
+    pub const fn new(r: bool, g: bool, b: bool, a: bool) -> Self {
+        Self { r, g, b, a }
+    }
+
+    fn apply(self, dst: Pixel, src: Pixel) -> Pixel {
+        Pixel {
+            red: if self.r { src.red } else { dst.red },
+            green: if self.g { src.green } else { dst.green },
+            blue: if self.b { src.blue } else { dst.blue },
+            alpha: if self.a { src.alpha } else { dst.alpha },
+        }
+    }
+}
+
+/// How [`FrameBuffer::blit_with`] combines a source pixel with the destination pixel.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BlendMode {
+    /// The source pixel overwrites the destination.
+    #[default]
+    Replace,
+    /// The source pixel is composited over the destination, see [`Pixel::over`].
+    AlphaOver,
+    /// Each channel is the sum of both, clamped to 255.
+    Additive,
+    /// Each channel is the product of both, scaled back to `0..=255`.
+    Multiply,
+}
+impl BlendMode {
+    fn blend_row(self, src: &[Pixel], dst: &mut [Pixel]) {
+        match self {
+            BlendMode::Replace => dst.copy_from_slice(src),
+            BlendMode::AlphaOver => {
+                for (dst, src) in dst.iter_mut().zip(src) {
+                    *dst = src.over(*dst);
+                }
+            }
+            BlendMode::Additive => blend_channels(src, dst, u8::saturating_add),
+            BlendMode::Multiply => {
+                blend_channels(src, dst, |a, b| ((a as u16 * b as u16 + 127) / 255) as u8)
+            }
+        }
+    }
+}
+fn blend_channels(src: &[Pixel], dst: &mut [Pixel], f: impl Fn(u8, u8) -> u8) {
+    for (dst, src) in dst.iter_mut().zip(src) {
+        *dst = Pixel {
+            red: f(src.red, dst.red),
+            green: f(src.green, dst.green),
+            blue: f(src.blue, dst.blue),
+            alpha: f(src.alpha, dst.alpha),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Range;
+
+    /// A 3x2 buffer with a distinct color in each corner.
+    fn corners() -> FrameBuffer {
+        let mut buffer = FrameBuffer::new(3, 2);
+        buffer.set_pixel(0, 0, Pixel::rgb(1, 0, 0));
+        buffer.set_pixel(2, 0, Pixel::rgb(2, 0, 0));
+        buffer.set_pixel(0, 1, Pixel::rgb(3, 0, 0));
+        buffer.set_pixel(2, 1, Pixel::rgb(4, 0, 0));
+        buffer
+    }
+    fn corner_colors(buffer: &FrameBuffer) -> [u8; 4] {
+        let (right, bottom) = (buffer.width() - 1, buffer.height() - 1);
+        [(0, 0), (right, 0), (0, bottom), (right, bottom)].map(|(x, y)| buffer.get_pixel(x, y).red)
+    }
+
+    #[test]
+    fn rotate90_non_square() {
+        let rotated = corners().rotate90();
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(corner_colors(&rotated), [3, 1, 4, 2]);
+    }
+
+    #[test]
+    fn rotate180_non_square() {
+        let rotated = corners().rotate180();
+        assert_eq!((rotated.width(), rotated.height()), (3, 2));
+        assert_eq!(corner_colors(&rotated), [4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn rotate270_non_square() {
+        let rotated = corners().rotate270();
+        assert_eq!((rotated.width(), rotated.height()), (2, 3));
+        assert_eq!(corner_colors(&rotated), [2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn rotations_round_trip() {
+        let buffer = corners();
+        assert_eq!(buffer.rotate90().rotate270(), buffer);
+        assert_eq!(buffer.rotate180().rotate180(), buffer);
+    }
+
+    #[test]
+    fn u32_slice_matches_to_u32() {
+        let mut buffer = FrameBuffer::new(3, 2);
+        buffer.set_pixel(0, 0, Pixel::rgba(1, 2, 3, 4));
+        assert_eq!(buffer.as_u32_slice()[0], buffer.get_pixel(0, 0).to_u32());
+        buffer.as_u32_slice_mut()[5] = Pixel::rgba(5, 6, 7, 8).to_u32();
+        assert_eq!(buffer.get_pixel(2, 1), Pixel::rgba(5, 6, 7, 8));
+    }
+
+    #[test]
+    fn u32_slice_of_empty_buffer() {
+        for (width, height) in [(0, 0), (4, 0), (0, 5)] {
+            let mut buffer = FrameBuffer::new(width, height);
+            assert!(buffer.as_u32_slice().is_empty());
+            assert!(buffer.as_u32_slice_mut().is_empty());
+        }
+    }
+
+    #[test]
+    fn from_unaligned_bytes() {
+        let bytes: Vec<u8> = (0..9).collect();
+        let buffer = FrameBuffer::from_bytes(2, 1, &bytes[1..]).unwrap();
+        assert_eq!(buffer.get_pixel(0, 0), Pixel::rgba(1, 2, 3, 4));
+        assert_eq!(buffer.get_pixel(1, 0), Pixel::rgba(5, 6, 7, 8));
+    }
+
+    #[test]
+    fn masked_writes_keep_other_channels() {
+        let mut buffer = FrameBuffer::new(4, 4);
+        buffer.fill(Pixel::rgba(10, 20, 30, 255));
+        for y in 1..3 {
+            for x in 1..3 {
+                buffer.set_pixel_masked(x, y, Pixel::rgba(0, 0, 0, 7), ChannelMask::ALPHA);
+            }
+        }
+
+        assert_eq!(buffer.get_pixel(1, 1), Pixel::rgba(10, 20, 30, 7));
+        assert_eq!(buffer.get_pixel(2, 2), Pixel::rgba(10, 20, 30, 7));
+        assert_eq!(buffer.get_pixel(0, 0), Pixel::rgba(10, 20, 30, 255));
+        assert_eq!(buffer.get_pixel(3, 2), Pixel::rgba(10, 20, 30, 255));
+    }
+
+    #[test]
+    fn fill_masked_writes_only_enabled_channels() {
+        let mut buffer = FrameBuffer::new(2, 2);
+        buffer.fill(Pixel::rgba(10, 20, 30, 40));
+        buffer.fill_masked(
+            Pixel::rgba(1, 2, 3, 4),
+            ChannelMask::new(true, false, true, false),
+        );
+        assert!(buffer
+            .pixels()
+            .iter()
+            .all(|p| *p == Pixel::rgba(1, 20, 3, 40)));
+    }
+
+    /// The leftmost column and topmost row with lit pixels in `rows`.
+    fn lit_origin(buffer: &FrameBuffer, rows: Range<usize>) -> (usize, usize) {
+        let lit: Vec<_> = buffer
+            .enumerate_pixels()
+            .filter(|(_, y, p)| rows.contains(y) && *p != Pixel::black())
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let left = lit.iter().map(|(x, _)| *x).min().unwrap();
+        let top = lit.iter().map(|(_, y)| *y).min().unwrap();
+        (left, top)
+    }
+
+    #[test]
+    fn text_centered_on_point() {
+        // Unknown characters draw the fallback box, which starts one column into its glyph.
+        let (left, top) = (1, 0);
+
+        let mut buffer = FrameBuffer::new(40, 20);
+        buffer.draw_text_centered(20, 10, "\u{1}\u{1}", Pixel::white(), 1);
+        assert_eq!(lit_origin(&buffer, 0..20), (20 - 8 + left, 10 - 4 + top));
+
+        let mut buffer = FrameBuffer::new(40, 20);
+        buffer.draw_text_centered(20, 10, "\u{1}\u{1}", Pixel::white(), 2);
+        assert_eq!(
+            lit_origin(&buffer, 0..20),
+            (20 - 16 + 2 * left, 10 - 8 + 2 * top)
+        );
+    }
+
+    #[test]
+    fn text_centers_each_line() {
+        let mut buffer = FrameBuffer::new(40, 20);
+        buffer.draw_text_centered(20, 10, "\u{1}\n\u{1}\u{1}\u{1}", Pixel::white(), 1);
+        assert_eq!(lit_origin(&buffer, 0..10).0, 20 - 4 + 1);
+        assert_eq!(lit_origin(&buffer, 10..20).0, 20 - 12 + 1);
+    }
+
+    #[test]
+    fn distance_black_to_white() {
+        let (black, white) = (Pixel::black(), Pixel::white());
+        assert_eq!(black.distance_squared(&white), 3 * 255 * 255);
+        assert_eq!(black.distance(&white), (3.0f32 * 255.0 * 255.0).sqrt());
+        assert_eq!(black.distance_squared(&black), 0);
+    }
+
+    #[test]
+    fn distance_ignores_alpha_unless_asked() {
+        let (opaque, clear) = (Pixel::rgba(9, 9, 9, 255), Pixel::rgba(9, 9, 9, 0));
+        assert_eq!(opaque.distance_squared(&clear), 0);
+        assert_eq!(opaque.distance_squared_rgba(&clear), 255 * 255);
+    }
+
+    #[test]
+    fn diff_one_modified_pixel() {
+        let reference = FrameBuffer::new(4, 3);
+        let mut modified = reference.clone();
+        modified.set_pixel(2, 1, Pixel::rgb(0, 100, 0));
+
+        let result = reference.diff_heatmap(&modified).unwrap();
+        assert_eq!(result.differing, 1);
+        assert_eq!(result.max_channel_delta, 100);
+        assert!(result.heatmap.get_pixel(2, 1).red > 0);
+        let cold = result
+            .heatmap
+            .enumerate_pixels()
+            .filter(|(x, y, _)| (*x, *y) != (2, 1))
+            .all(|(_, _, p)| p == Pixel::black());
+        assert!(cold);
+    }
+
+    #[test]
+    fn diff_rejects_mismatched_sizes() {
+        let error = FrameBuffer::new(4, 3)
+            .diff_heatmap(&FrameBuffer::new(3, 4))
+            .err();
+        let expected = DimensionMismatch {
+            expected: (4, 3),
+            found: (3, 4),
+        };
+        assert_eq!(error, Some(expected));
+    }
+
+    fn dirty_row_indices(buffer: &FrameBuffer) -> Vec<usize> {
+        buffer.dirty_rows().map(|(y, _)| y).collect()
+    }
+
+    #[test]
+    fn dirty_rows_yields_only_written_rows() {
+        let mut buffer = FrameBuffer::new(4, 8);
+        buffer.clear_dirty();
+        buffer.set_pixel(1, 2, Pixel::white());
+        buffer.set_pixel(3, 5, Pixel::white());
+
+        let rows: Vec<_> = buffer.dirty_rows().collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].0, 2);
+        assert_eq!(rows[0].1[1], Pixel::white());
+        assert_eq!(rows[1].0, 5);
+        assert_eq!(rows[1].1[3], Pixel::white());
+
+        buffer.clear_dirty();
+        assert!(dirty_row_indices(&buffer).is_empty());
+
+        buffer.mark_all_dirty();
+        assert_eq!(dirty_row_indices(&buffer), (0..8).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn circles_near_the_i32_limits_clip_instead_of_overflowing() {
+        let mut buffer = FrameBuffer::new(4, 4);
+        buffer.draw_circle(i32::MAX, i32::MIN, 3, Pixel::white());
+        buffer.fill_circle(i32::MIN, i32::MAX, 3, Pixel::white());
+        buffer.fill_ellipse(i32::MAX, i32::MAX, 3, 2, Pixel::white());
+        assert!(buffer.pixels().iter().all(|p| *p == Pixel::black()));
+    }
+
+    #[test]
+    fn oversized_buffers_are_rejected() {
+        let overflow = SizeOverflow {
+            width: usize::MAX / 2,
+            height: 3,
+        };
+        assert_eq!(
+            FrameBuffer::try_new(usize::MAX / 2, 3).err(),
+            Some(overflow)
+        );
+        // The pixel count alone fits, but not its size in bytes.
+        assert!(FrameBuffer::try_new(usize::MAX / 4, 2).is_err());
+
+        let pixels = FrameBuffer::from_pixels(usize::MAX / 2, 3, Vec::new());
+        assert_eq!(pixels.err(), Some(SizeError::Overflow(overflow)));
+        let bytes = FrameBuffer::from_bytes(usize::MAX / 2, 3, &[]);
+        assert_eq!(bytes.err(), Some(SizeError::Overflow(overflow)));
+    }
+
+    #[test]
+    fn oversized_resize_keeps_the_buffer() {
+        let mut buffer = corners();
+        assert!(buffer.resize_keep(usize::MAX, 2, Pixel::white()).is_err());
+        assert_eq!((buffer.width(), buffer.height()), (3, 2));
+        assert_eq!(corner_colors(&buffer), [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn diff_reports_count_and_bounds() {
+        let reference = FrameBuffer::new(5, 4);
+        assert_eq!(reference.diff(&reference.clone()), None);
+
+        let mut modified = reference.clone();
+        modified.set_pixel(1, 2, Pixel::white());
+        modified.set_pixel(3, 1, Pixel::white());
+        let expected = FrameDiff {
+            differing: 2,
+            bounds: (1, 1, 3, 2),
+        };
+        assert_eq!(reference.diff(&modified), Some(expected));
+    }
+
+    #[test]
+    fn diff_counts_uncovered_pixels_when_sizes_differ() {
+        let diff = FrameBuffer::new(2, 2).diff(&FrameBuffer::new(3, 2));
+        let expected = FrameDiff {
+            differing: 2,
+            bounds: (2, 0, 1, 2),
+        };
+        assert_eq!(diff, Some(expected));
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn save_diff_png_rejects_mismatched_sizes() {
+        let path = std::env::temp_dir().join("pixely_save_diff_png_mismatch.png");
+        let error = FrameBuffer::new(2, 2)
+            .save_diff_png(&FrameBuffer::new(3, 2), &path)
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn large_ellipses_clip_instead_of_overflowing() {
+        let mut buffer = FrameBuffer::new(4, 4);
+        buffer.fill_ellipse(0, 0, 2_000_000, 2_000_000, Pixel::white());
+        assert!(buffer.pixels().iter().all(|p| *p == Pixel::white()));
+
+        // Only the rows that land in the buffer are computed, so this doesn't allocate for
+        // every row of the ellipse.
+        let mut buffer = FrameBuffer::new(4, 4);
+        buffer.fill_ellipse(1, 2_000_001, 3, 2_000_000, Pixel::white());
+        assert_eq!(buffer.get_pixel(1, 1), Pixel::white());
+        assert_eq!(buffer.get_pixel(1, 0), Pixel::black());
+    }
+
+    #[test]
+    fn resize_scaled_nearest_makes_blocks() {
+        let mut buffer = FrameBuffer::new(2, 2);
+        buffer.set_pixel(0, 0, Pixel::rgb(1, 0, 0));
+        buffer.set_pixel(1, 0, Pixel::rgb(2, 0, 0));
+        buffer.set_pixel(0, 1, Pixel::rgb(3, 0, 0));
+        buffer.set_pixel(1, 1, Pixel::rgb(4, 0, 0));
+        buffer.resize_scaled(4, 4, false).unwrap();
+
+        assert_eq!((buffer.width(), buffer.height()), (4, 4));
+        for (x, y, pixel) in buffer.enumerate_pixels() {
+            let source = (1 + x / 2 + 2 * (y / 2)) as u8;
+            assert_eq!(pixel.red, source, "({x}, {y})");
+        }
+        assert_eq!(buffer.dirty_rect(), Some((0, 0, 4, 4)));
+    }
+
+    #[test]
+    fn shift_channels_moves_only_red() {
+        let mut buffer = FrameBuffer::new(4, 2);
+        for (x, y) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+            buffer.set_pixel(x, y, Pixel::rgba(255, 255, 255, 200));
+        }
+        for (x, y) in [(2, 0), (3, 0), (2, 1), (3, 1)] {
+            buffer.set_pixel(x, y, Pixel::rgba(0, 0, 0, 100));
+        }
+        let before = buffer.clone();
+        buffer.shift_channels((1, 0), (0, 0), (0, 0));
+
+        for y in 0..2 {
+            let reds: Vec<_> = (0..4).map(|x| buffer.get_pixel(x, y).red).collect();
+            assert_eq!(reds, [255, 255, 255, 0]);
+            for x in 0..4 {
+                let (old, new) = (before.get_pixel(x, y), buffer.get_pixel(x, y));
+                assert_eq!((new.green, new.blue), (old.green, old.blue));
+                assert_eq!(new.alpha, old.alpha);
+            }
         }
     }
 }
-unsafe impl Pod for Pixel {}
-unsafe impl Zeroable for Pixel {}
@@ -0,0 +1,356 @@
+use crate::{
+    cast::cast_slice,
+    create_texture_bind_group,
+    framebuffer::{OutOfBounds, Pixel, SizeOverflow},
+    ColorSpace,
+};
+use std::mem::size_of;
+use wgpu::{
+    include_wgsl, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
+    ColorTargetState, ColorWrites, CommandEncoder, Device, Extent3d, FragmentState,
+    ImageDataLayout, LoadOp, MultisampleState, Operations, PipelineLayoutDescriptor,
+    PrimitiveState, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, Sampler, ShaderModule, ShaderStages, Texture, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureView,
+    TextureViewDimension, VertexState,
+};
+
+/// An 8-bit indexed image, whose pixels are looked up in a 256 color palette on the GPU.
+///
+/// Changing only the palette doesn't upload the indices again, which makes palette cycling
+/// effects cheap.
+pub struct IndexedFrameBuffer {
+    width: usize,
+    height: usize,
+    indices: Box<[u8]>,
+    palette: Box<[Pixel; 256]>,
+    indices_changed: bool,
+    palette_changed: bool,
+}
+impl IndexedFrameBuffer {
+    /// A buffer of index 0, with every palette entry black.
+    pub(crate) fn new(width: usize, height: usize) -> Result<Self, SizeOverflow> {
+        Ok(Self {
+            width,
+            height,
+            indices: zeroed_indices(width, height)?,
+            palette: Box::new([Pixel::black(); 256]),
+            indices_changed: true,
+            palette_changed: true,
+        })
+    }
+    /// Keeps the palette, resetting all indices to 0.
+    pub(crate) fn resize(&mut self, width: usize, height: usize) -> Result<(), SizeOverflow> {
+        self.indices = zeroed_indices(width, height)?;
+        self.width = width;
+        self.height = height;
+        self.indices_changed = true;
+        Ok(())
+    }
+    pub(crate) fn mark_all_changed(&mut self) {
+        self.indices_changed = true;
+        self.palette_changed = true;
+    }
+    pub(crate) fn changed(&self) -> bool {
+        self.indices_changed || self.palette_changed
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    pub fn get_index(&self, x: usize, y: usize) -> u8 {
+        let i = self.bounds_check(x, y).unwrap_or_else(|e| panic!("{e}"));
+        self.indices[i]
+    }
+    pub fn set_index(&mut self, x: usize, y: usize, index: u8) {
+        self.set_index_checked(x, y, index)
+            .unwrap_or_else(|e| panic!("{e}"));
+    }
+    pub fn set_index_checked(&mut self, x: usize, y: usize, index: u8) -> Result<(), OutOfBounds> {
+        let i = self.bounds_check(x, y)?;
+        self.indices[i] = index;
+        self.indices_changed = true;
+        Ok(())
+    }
+    pub fn fill(&mut self, index: u8) {
+        self.indices.fill(index);
+        self.indices_changed = true;
+    }
+    /// All indices in row-major order.
+    pub fn indices(&self) -> &[u8] {
+        &self.indices
+    }
+    pub fn indices_mut(&mut self) -> &mut [u8] {
+        self.indices_changed = true;
+        &mut self.indices
+    }
+
+    pub fn palette(&self) -> &[Pixel; 256] {
+        &self.palette
+    }
+    pub fn set_palette_entry(&mut self, index: u8, color: Pixel) {
+        self.palette[index as usize] = color;
+        self.palette_changed = true;
+    }
+    /// Replaces the palette entries from 0 up to `colors.len()`, ignoring any past 255.
+    pub fn set_palette(&mut self, colors: &[Pixel]) {
+        let len = colors.len().min(256);
+        self.palette[..len].copy_from_slice(&colors[..len]);
+        self.palette_changed = true;
+    }
+
+    fn bounds_check(&self, x: usize, y: usize) -> Result<usize, OutOfBounds> {
+        if x < self.width && y < self.height {
+            Ok(y * self.width + x)
+        } else {
+            Err(OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            })
+        }
+    }
+}
+
+fn zeroed_indices(width: usize, height: usize) -> Result<Box<[u8]>, SizeOverflow> {
+    let len = width
+        .checked_mul(height)
+        .ok_or(SizeOverflow { width, height })?;
+    Ok(vec![0; len].into_boxed_slice())
+}
+
+/// Turns an [`IndexedFrameBuffer`] into an RGBA texture the quad is drawn from, so scaling,
+/// layers and post effects work the same as with a [`crate::framebuffer::FrameBuffer`].
+pub(crate) struct PaletteLookup {
+    shader: ShaderModule,
+    bind_group_layout: BindGroupLayout,
+    /// The pipeline and the format of the texture it writes.
+    pipeline: Option<(RenderPipeline, TextureFormat)>,
+    gpu: Option<LookupTextures>,
+}
+struct LookupTextures {
+    indices: Texture,
+    palette: Texture,
+    lookup_bind_group: BindGroup,
+    output: Texture,
+    output_view: TextureView,
+    /// Binds `output` with the quad's bind group layout.
+    quad_bind_group: BindGroup,
+}
+impl PaletteLookup {
+    pub(crate) fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(include_wgsl!("indexed.wgsl"));
+        let texture_entry = |binding, sample_type| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type,
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                texture_entry(0, TextureSampleType::Uint),
+                texture_entry(1, TextureSampleType::Float { filterable: false }),
+            ],
+        });
+
+        Self {
+            shader,
+            bind_group_layout,
+            pipeline: None,
+            gpu: None,
+        }
+    }
+
+    pub(crate) fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        buffer: &mut IndexedFrameBuffer,
+        quad_layout: &BindGroupLayout,
+        sampler: &Sampler,
+        color_space: ColorSpace,
+    ) {
+        let format = color_space.texture_format();
+        if self.pipeline.as_ref().map(|(_, f)| *f) != Some(format) {
+            self.pipeline = Some((self.create_pipeline(device, format), format));
+        }
+
+        let stale = match &self.gpu {
+            Some(gpu) => {
+                (gpu.output.width() as usize, gpu.output.height() as usize)
+                    != (buffer.width, buffer.height)
+                    || gpu.output.format() != format
+            }
+            None => true,
+        };
+        if stale {
+            self.gpu = Some(self.create_textures(device, buffer, quad_layout, sampler, format));
+            buffer.mark_all_changed();
+        }
+
+        let gpu = self.gpu.as_ref().unwrap();
+        if buffer.indices_changed {
+            queue.write_texture(
+                gpu.indices.as_image_copy(),
+                &buffer.indices,
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(buffer.width as u32),
+                    rows_per_image: Some(buffer.height as u32),
+                },
+                gpu.indices.size(),
+            );
+            buffer.indices_changed = false;
+        }
+        if buffer.palette_changed {
+            queue.write_texture(
+                gpu.palette.as_image_copy(),
+                cast_slice(&buffer.palette[..]),
+                ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some((256 * size_of::<Pixel>()) as u32),
+                    rows_per_image: Some(1),
+                },
+                gpu.palette.size(),
+            );
+            buffer.palette_changed = false;
+        }
+    }
+    /// The bind group to draw the quad with, once [`PaletteLookup::prepare`] has run.
+    pub(crate) fn quad_bind_group(&self) -> Option<&BindGroup> {
+        self.gpu.as_ref().map(|gpu| &gpu.quad_bind_group)
+    }
+    pub(crate) fn recreate_bind_group(
+        &mut self,
+        device: &Device,
+        quad_layout: &BindGroupLayout,
+        sampler: &Sampler,
+    ) {
+        if let Some(gpu) = &mut self.gpu {
+            gpu.quad_bind_group =
+                create_texture_bind_group(device, quad_layout, &gpu.output, sampler);
+        }
+    }
+    pub(crate) fn draw(&self, cmd: &mut CommandEncoder) {
+        let (Some((pipeline, _)), Some(gpu)) = (&self.pipeline, &self.gpu) else {
+            return;
+        };
+        let mut pass = cmd.begin_render_pass(&RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &gpu.output_view,
+                resolve_target: None,
+                ops: Operations {
+                    store: true,
+                    load: LoadOp::Load,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &gpu.lookup_bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn create_pipeline(&self, device: &Device, format: TextureFormat) -> RenderPipeline {
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&self.bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: "vertex_main",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: "fragment_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        })
+    }
+    fn create_textures(
+        &self,
+        device: &Device,
+        buffer: &IndexedFrameBuffer,
+        quad_layout: &BindGroupLayout,
+        sampler: &Sampler,
+        format: TextureFormat,
+    ) -> LookupTextures {
+        let texture = |width: usize, height: usize, format, usage| {
+            device.create_texture(&TextureDescriptor {
+                label: None,
+                size: Extent3d {
+                    width: width as u32,
+                    height: height as u32,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let upload = TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING;
+        let indices = texture(buffer.width, buffer.height, TextureFormat::R8Uint, upload);
+        let palette = texture(256, 1, format, upload);
+        let output = texture(
+            buffer.width,
+            buffer.height,
+            format,
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+        );
+
+        let indices_view = indices.create_view(&Default::default());
+        let palette_view = palette.create_view(&Default::default());
+        let lookup_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&indices_view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&palette_view),
+                },
+            ],
+        });
+        let output_view = output.create_view(&Default::default());
+        let quad_bind_group = create_texture_bind_group(device, quad_layout, &output, sampler);
+
+        LookupTextures {
+            indices,
+            palette,
+            lookup_bind_group,
+            output,
+            output_view,
+            quad_bind_group,
+        }
+    }
+}
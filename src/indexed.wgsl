@@ -0,0 +1,15 @@
+@group(0) @binding(0) var indices: texture_2d<u32>;
+@group(0) @binding(1) var palette: texture_2d<f32>;
+
+// A single triangle covering the whole target.
+@vertex
+fn vertex_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+}
+
+@fragment
+fn fragment_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let index = textureLoad(indices, vec2<i32>(position.xy), 0).r;
+    return textureLoad(palette, vec2<i32>(i32(index), 0), 0);
+}
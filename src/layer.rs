@@ -0,0 +1,75 @@
+use crate::{
+    create_framebuffer_texture, create_texture_bind_group, framebuffer::FrameBuffer,
+    texture_matches, write_framebuffer_region, ColorSpace,
+};
+use wgpu::{BindGroup, BindGroupLayout, Device, Queue, Sampler, Texture};
+
+/// Identifies a layer added with [`crate::Pixely::add_layer`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LayerId(pub(crate) usize);
+
+pub(crate) struct Layer {
+    pub(crate) framebuffer: FrameBuffer,
+    pub(crate) visible: bool,
+    gpu: Option<(Texture, BindGroup)>,
+}
+impl Layer {
+    pub(crate) fn new(framebuffer: FrameBuffer) -> Self {
+        Self {
+            framebuffer,
+            visible: true,
+            gpu: None,
+        }
+    }
+
+    pub(crate) fn bind_group(&self) -> Option<&BindGroup> {
+        match self.visible {
+            true => self.gpu.as_ref().map(|(_, bind_group)| bind_group),
+            false => None,
+        }
+    }
+    pub(crate) fn is_empty(&self) -> bool {
+        self.framebuffer.width() == 0 || self.framebuffer.height() == 0
+    }
+
+    pub(crate) fn prepare(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+        color_space: ColorSpace,
+    ) {
+        if self.is_empty() {
+            self.gpu = None;
+            return;
+        }
+
+        let stale = match &self.gpu {
+            Some((texture, _)) => !texture_matches(texture, &self.framebuffer, color_space),
+            None => true,
+        };
+        if stale {
+            let texture = create_framebuffer_texture(device, &self.framebuffer, color_space);
+            let bind_group = create_texture_bind_group(device, layout, &texture, sampler);
+            self.gpu = Some((texture, bind_group));
+            self.framebuffer.mark_all_dirty();
+        }
+
+        if let Some(region) = self.framebuffer.dirty_rect() {
+            let (texture, _) = self.gpu.as_ref().unwrap();
+            write_framebuffer_region(queue, texture, &self.framebuffer, region);
+            self.framebuffer.clear_dirty();
+        }
+    }
+    pub(crate) fn recreate_bind_group(
+        &mut self,
+        device: &Device,
+        layout: &BindGroupLayout,
+        sampler: &Sampler,
+    ) {
+        if let Some((texture, bind_group)) = &mut self.gpu {
+            *bind_group = create_texture_bind_group(device, layout, texture, sampler);
+        }
+    }
+}
@@ -1,61 +1,195 @@
-use bytemuck::{cast_slice, Pod, Zeroable};
-use framebuffer::{FrameBuffer, Pixel};
+use builder::PixelyBuilder;
+use cast::cast_slice;
+use framebuffer::{FrameBuffer, Pixel, SizeOverflow};
+use indexed::{IndexedFrameBuffer, PaletteLookup};
+use layer::{Layer, LayerId};
+use post::{crt_shader, pop_shader_errors, CrtParams, PostEffect, ShaderError};
 use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
-use std::{iter::once, mem::size_of};
+use std::{
+    error::Error,
+    fmt,
+    iter::once,
+    mem::size_of,
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+use timing::GpuTimer;
+use view::FrameBufferViewMut;
 use wgpu::{
-    include_wgsl, Adapter, AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry,
-    BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
-    BlendState, Buffer, BufferDescriptor, BufferUsages, Color, ColorTargetState, ColorWrites,
-    CompositeAlphaMode, CreateSurfaceError, Device, Extent3d, Face, FilterMode, FragmentState,
-    FrontFace, ImageDataLayout, IndexFormat, Instance, LoadOp, MultisampleState, Operations,
-    PipelineLayoutDescriptor, PolygonMode, PresentMode, PrimitiveState, PrimitiveTopology, Queue,
-    RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor,
-    Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages, Surface, SurfaceConfiguration,
-    SurfaceError, Texture, TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType,
-    TextureUsages, TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexFormat,
-    VertexState, VertexStepMode,
+    include_wgsl, Adapter, AdapterInfo, AddressMode, BindGroup, BindGroupDescriptor,
+    BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry,
+    BindingResource, BindingType, BlendState, Buffer, BufferAsyncError, BufferDescriptor,
+    BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoder, CompositeAlphaMode,
+    CreateSurfaceError, Device, DeviceDescriptor, ErrorFilter, Extent3d, Face, Features,
+    FilterMode, FragmentState, FrontFace, ImageCopyBuffer, ImageDataLayout, IndexFormat, Instance,
+    Limits, LoadOp, Maintain, MapMode, MultisampleState, Operations, Origin3d, PipelineLayout,
+    PipelineLayoutDescriptor, PolygonMode, PowerPreference, PresentMode, PrimitiveState,
+    PrimitiveTopology, Queue, RenderPassColorAttachment, RenderPassDescriptor, RenderPipeline,
+    RenderPipelineDescriptor, RequestAdapterOptions, RequestDeviceError, Sampler,
+    SamplerBindingType, SamplerDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, Surface, SurfaceConfiguration, SurfaceError, SurfaceTexture, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureFormatFeatureFlags,
+    TextureSampleType, TextureUsages, TextureView, TextureViewDimension, VertexAttribute,
+    VertexBufferLayout, VertexFormat, VertexState, VertexStepMode, COPY_BYTES_PER_ROW_ALIGNMENT,
 };
 
+pub mod builder;
+pub mod canvas;
+mod cast;
 pub mod framebuffer;
-
-const FRAMEBUFFER_TEXTURE_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+pub mod indexed;
+pub mod layer;
+pub mod post;
+#[cfg(feature = "softbuffer-fallback")]
+pub mod software;
+pub mod text;
+mod timing;
+pub mod view;
 
 pub struct Pixely {
     framebuffer: FrameBuffer,
-    framebuffer_changed: bool,
+    layers: Vec<Layer>,
+    composition_changed: bool,
+    /// Drawn instead of `framebuffer` while indexed mode is on.
+    indexed: Option<(IndexedFrameBuffer, PaletteLookup)>,
 
-    surface: Surface,
+    /// `None` for headless renderers.
+    surface: Option<Surface>,
     config: SurfaceConfiguration,
     surface_changed: bool,
+    present_modes: Vec<PresentMode>,
+    adapter_info: AdapterInfo,
 
     pipeline: RenderPipeline,
+    layer_pipeline: RenderPipeline,
+    sample_count: u32,
+    /// What the quad is drawn into before being resolved, when `sample_count` is above one.
+    msaa_target: Option<(Texture, TextureView)>,
     texture: Option<Texture>,
     sampler: Sampler,
+    min_filter: ScalingFilter,
+    mag_filter: ScalingFilter,
+    address_mode: AddressMode,
     bind_group_layout: BindGroupLayout,
     bind_group: Option<BindGroup>,
     vertex_buffer: Buffer,
     vertices_changed: bool,
+    /// The quad size and rotation the vertex buffer currently holds.
+    written_vertices: Option<[Vertex; 4]>,
     index_buffer: Buffer,
+    clear_color: Pixel,
+    scale_mode: ScaleMode,
+    alignment: Alignment,
+    rotation: Rotation,
+    scroll: (f32, f32),
+    color_space: ColorSpace,
+    post_effect: Option<PostEffect>,
+
+    strict: bool,
+    frame_interval: Option<Duration>,
+    last_present: Option<Instant>,
+    gpu_timer: Option<GpuTimer>,
+    /// Only ever accessed through `&mut self`, the mutex just keeps `Pixely` `Sync`.
+    on_frame_presented: Option<Mutex<Box<dyn FnMut() + Send>>>,
+
+    /// The surface size in window coordinates; `config` holds it in physical pixels.
+    logical_size: (usize, usize),
+    scale_factor: f64,
 }
 impl Pixely {
+    pub fn builder<'a, W: HasRawWindowHandle + HasRawDisplayHandle>() -> PixelyBuilder<'a, W> {
+        PixelyBuilder::new()
+    }
     pub fn new<W: HasRawWindowHandle + HasRawDisplayHandle>(
         desc: PixelyDesc<W>,
-    ) -> Result<Self, CreateSurfaceError> {
+    ) -> Result<Self, PixelyError> {
         let surface = unsafe { desc.instance.create_surface(desc.window.window) }?;
-        let surface_format = TextureFormat::Bgra8UnormSrgb;
+        let capabilities = surface.get_capabilities(desc.adapter);
+        let present_modes = capabilities.present_modes;
+        let alpha_mode = supported_alpha_mode(&capabilities.alpha_modes, desc.window.alpha_mode);
+        let surface_format = preferred_surface_format(&capabilities.formats)
+            .ok_or(PixelyError::UnsupportedSurfaceFormat)?;
+        let logical_size = (desc.window.width, desc.window.height);
+        let scale_factor = valid_scale_factor(desc.window.scale_factor);
+        let (width, height) = physical_size(logical_size, scale_factor);
         let config = SurfaceConfiguration {
             usage: TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: desc.window.width as u32,
-            height: desc.window.height as u32,
+            width,
+            height,
+            present_mode: supported_present_mode(&present_modes, desc.window.present_mode),
+            alpha_mode,
+            view_formats: [surface_format].into(),
+        };
+        let parts = Parts {
+            buffer: desc.buffer,
+            scaling_filter: desc.scaling_filter,
+            address_mode: desc.address_mode,
+            scale_mode: desc.scale_mode,
+            color_space: desc.color_space,
+            alpha_format: desc.alpha_format,
+            sample_count: desc.sample_count,
+            shader: desc.shader,
+            adapter: desc.adapter,
+            device: desc.device,
+            queue: desc.queue,
+        };
+        let mut pixely = Self::from_parts(Some(surface), config, present_modes, parts)?;
+        pixely.logical_size = logical_size;
+        pixely.scale_factor = scale_factor;
+        Ok(pixely)
+    }
+    /// Creates a renderer without a window. [`Pixely::render`] does nothing for it; frames are
+    /// read back with [`Pixely::capture_frame`], in [`TextureFormat::Rgba8UnormSrgb`].
+    pub fn new_headless(desc: HeadlessDesc) -> Result<Self, PixelyError> {
+        let format = TextureFormat::Rgba8UnormSrgb;
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format,
+            width: desc.width as u32,
+            height: desc.height as u32,
             present_mode: PresentMode::Fifo,
             alpha_mode: CompositeAlphaMode::Opaque,
-            view_formats: [surface_format].into(),
+            view_formats: [format].into(),
+        };
+        let parts = Parts {
+            buffer: desc.buffer,
+            scaling_filter: desc.scaling_filter,
+            address_mode: desc.address_mode,
+            scale_mode: desc.scale_mode,
+            color_space: desc.color_space,
+            alpha_format: desc.alpha_format,
+            sample_count: desc.sample_count,
+            shader: desc.shader,
+            adapter: desc.adapter,
+            device: desc.device,
+            queue: desc.queue,
         };
-        let framebuffer = FrameBuffer::new(desc.buffer.width, desc.buffer.height);
+        Self::from_parts(None, config, Vec::new(), parts)
+    }
+    fn from_parts(
+        surface: Option<Surface>,
+        config: SurfaceConfiguration,
+        present_modes: Vec<PresentMode>,
+        desc: Parts,
+    ) -> Result<Self, PixelyError> {
+        let surface_format = config.format;
+        let alpha_mode = config.alpha_mode;
+        let logical_size = (config.width as usize, config.height as usize);
+        let sample_count = supported_sample_count(desc.adapter, surface_format, desc.sample_count);
+        let framebuffer = FrameBuffer::try_new(desc.buffer.width, desc.buffer.height)?;
 
-        let shader_src = include_wgsl!("shader.wgsl");
-        let shader_mod = desc.device.create_shader_module(shader_src);
+        desc.device.push_error_scope(ErrorFilter::Validation);
+        let shader_mod = match desc.shader {
+            Some(wgsl) => desc.device.create_shader_module(ShaderModuleDescriptor {
+                label: None,
+                source: ShaderSource::Wgsl(wgsl.into()),
+            }),
+            None => desc
+                .device
+                .create_shader_module(include_wgsl!("shader.wgsl")),
+        };
         let bind_group_layout = desc
             .device
             .create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -86,67 +220,25 @@ impl Pixely {
                 bind_group_layouts: &[&bind_group_layout],
                 push_constant_ranges: &[],
             });
-        let pipeline = desc
-            .device
-            .create_render_pipeline(&RenderPipelineDescriptor {
-                label: None,
-                layout: Some(&pipeline_layout),
-                vertex: VertexState {
-                    module: &shader_mod,
-                    entry_point: "vertex_main",
-                    buffers: &[VertexBufferLayout {
-                        array_stride: size_of::<Vertex>() as u64,
-                        step_mode: VertexStepMode::Vertex,
-                        attributes: &[
-                            VertexAttribute {
-                                format: VertexFormat::Float32x2,
-                                offset: 0,
-                                shader_location: 0,
-                            },
-                            VertexAttribute {
-                                format: VertexFormat::Float32x2,
-                                offset: 2 * size_of::<f32>() as u64,
-                                shader_location: 1,
-                            },
-                        ],
-                    }],
-                },
-                primitive: PrimitiveState {
-                    topology: PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: FrontFace::Cw,
-                    cull_mode: Some(Face::Back),
-                    unclipped_depth: false,
-                    polygon_mode: PolygonMode::Fill,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                fragment: Some(FragmentState {
-                    module: &shader_mod,
-                    entry_point: "fragment_main",
-                    targets: &[Some(ColorTargetState {
-                        format: surface_format,
-                        blend: Some(BlendState::REPLACE),
-                        write_mask: ColorWrites::ALL,
-                    })],
-                }),
-                multiview: None,
-            });
-        let sampler = desc.device.create_sampler(&SamplerDescriptor {
-            label: None,
-            address_mode_u: AddressMode::Repeat,
-            address_mode_v: AddressMode::Repeat,
-            address_mode_w: AddressMode::Repeat,
-            mag_filter: FilterMode::Nearest,
-            min_filter: FilterMode::Linear,
-            mipmap_filter: FilterMode::Nearest,
-            ..Default::default()
-        });
+        let pipeline = create_pipeline(
+            desc.device,
+            &pipeline_layout,
+            &shader_mod,
+            surface_format,
+            blend_state(alpha_mode, desc.alpha_format),
+            sample_count,
+        );
+        let layer_pipeline = create_pipeline(
+            desc.device,
+            &pipeline_layout,
+            &shader_mod,
+            surface_format,
+            desc.alpha_format.blend_state(),
+            sample_count,
+        );
+        pop_shader_errors(desc.device)?;
+        let filter = desc.scaling_filter;
+        let sampler = create_sampler(desc.device, filter, filter, desc.address_mode);
 
         let indices = [0u16, 1, 2, 1, 3, 2];
         let index_buffer = desc.device.create_buffer(&BufferDescriptor {
@@ -167,148 +259,721 @@ impl Pixely {
 
         Ok(Self {
             framebuffer,
-            framebuffer_changed: true,
+            layers: Vec::new(),
+            composition_changed: false,
+            indexed: None,
             surface,
             config,
             surface_changed: true,
+            present_modes,
+            adapter_info: desc.adapter.get_info(),
             pipeline,
+            layer_pipeline,
+            sample_count,
+            msaa_target: None,
             texture: None,
             sampler,
+            min_filter: desc.scaling_filter,
+            mag_filter: desc.scaling_filter,
+            address_mode: desc.address_mode,
             bind_group_layout,
             bind_group: None,
             vertex_buffer,
             vertices_changed: true,
+            written_vertices: None,
             index_buffer,
+            clear_color: Pixel::black(),
+            scale_mode: desc.scale_mode,
+            alignment: Alignment::Center,
+            rotation: Rotation::None,
+            scroll: (0.0, 0.0),
+            color_space: desc.color_space,
+            post_effect: None,
+            strict: false,
+            frame_interval: None,
+            last_present: None,
+            gpu_timer: None,
+            on_frame_presented: None,
+            logical_size,
+            scale_factor: 1.0,
         })
     }
 
     fn recreate_texture(&mut self, device: &Device) {
-        let texture = device.create_texture(&TextureDescriptor {
-            label: None,
-            size: Extent3d {
-                width: self.framebuffer.width() as u32,
-                height: self.framebuffer.height() as u32,
-                depth_or_array_layers: 1,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: FRAMEBUFFER_TEXTURE_FORMAT,
-            usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[FRAMEBUFFER_TEXTURE_FORMAT],
-        });
-        let view = texture.create_view(&Default::default());
-
-        let bind_group = device.create_bind_group(&BindGroupDescriptor {
-            label: None,
-            layout: &self.bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&view),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&self.sampler),
-                },
-            ],
-        });
-
-        self.texture = Some(texture);
+        self.texture = Some(create_framebuffer_texture(
+            device,
+            &self.framebuffer,
+            self.color_space,
+        ));
+        self.recreate_bind_group(device);
+    }
+    fn recreate_bind_group(&mut self, device: &Device) {
+        let Some(texture) = &self.texture else {
+            return;
+        };
+        let bind_group =
+            create_texture_bind_group(device, &self.bind_group_layout, texture, &self.sampler);
         self.bind_group = Some(bind_group);
     }
+    fn skip_reason(&self, target_size: (u32, u32)) -> Option<SkipReason> {
+        if target_size.0 == 0 || target_size.1 == 0 {
+            Some(SkipReason::ZeroSizedSurface)
+        } else if self.framebuffer.width() == 0 || self.framebuffer.height() == 0 {
+            Some(SkipReason::ZeroSizedFramebuffer)
+        } else {
+            None
+        }
+    }
+    fn texture_is_stale(&self) -> bool {
+        match &self.texture {
+            Some(texture) => !texture_matches(texture, &self.framebuffer, self.color_space),
+            None => true,
+        }
+    }
     fn reconfigure_surface(&mut self, device: &Device) {
-        self.surface.configure(device, &self.config);
+        if let Some(surface) = &self.surface {
+            surface.configure(device, &self.config);
+        }
         self.surface_changed = false;
     }
     fn upload_texture(&mut self, queue: &Queue) {
+        let frame_width = self.framebuffer.width();
+        let frame_height = self.framebuffer.height();
+        self.upload_region(queue, (0, 0, frame_width, frame_height));
+    }
+    fn upload_dirty_region(&mut self, queue: &Queue) {
+        if let Some(region) = self.framebuffer.dirty_rect() {
+            self.upload_region(queue, region);
+        }
+    }
+    fn upload_region(&mut self, queue: &Queue, region: (usize, usize, usize, usize)) {
         let texture = self.texture.as_ref().unwrap();
-        let image_copy = texture.as_image_copy();
-        let layout = ImageDataLayout {
-            offset: 0,
-            bytes_per_row: Some((self.framebuffer.width() * size_of::<Pixel>()) as u32),
-            rows_per_image: Some(self.framebuffer.height() as u32),
-        };
-        let size = Extent3d {
-            width: self.framebuffer.width() as u32,
-            height: self.framebuffer.height() as u32,
-            depth_or_array_layers: 1,
-        };
-        queue.write_texture(image_copy, self.framebuffer.as_bytes(), layout, size);
-        self.framebuffer_changed = false;
+        write_framebuffer_region(queue, texture, &self.framebuffer, region);
+        self.framebuffer.clear_dirty();
     }
     fn update_vertex_buffer(&mut self, queue: &Queue) {
-        let (width, height) = self.get_quad_size();
+        self.write_vertices(queue, self.get_quad_size());
+        self.vertices_changed = false;
+    }
+    fn write_vertices(&mut self, queue: &Queue, quad_size: (f32, f32)) {
+        let (scroll_x, scroll_y) = self.scroll;
+        let offset = [
+            scroll_x / self.framebuffer.width().max(1) as f32,
+            scroll_y / self.framebuffer.height().max(1) as f32,
+        ];
+        let (width, height) = quad_size;
+        let (align_x, align_y) = self.alignment.factors();
+        let x = (1.0 - width) * (2.0 * align_x - 1.0);
+        let y = (1.0 - height) * (1.0 - 2.0 * align_y);
+        let [bottom_left, top_left, bottom_right, top_right] = self
+            .rotation
+            .tex_coords()
+            .map(|[u, v]| [u + offset[0], v + offset[1]]);
         let vertices = [
-            vertex([-width, -height], [0.0, 1.0]),
-            vertex([-width, height], [0.0, 0.0]),
-            vertex([width, -height], [1.0, 1.0]),
-            vertex([width, height], [1.0, 0.0]),
+            vertex([x - width, y - height], bottom_left),
+            vertex([x - width, y + height], top_left),
+            vertex([x + width, y - height], bottom_right),
+            vertex([x + width, y + height], top_right),
         ];
+        if self.written_vertices == Some(vertices) {
+            return;
+        }
 
         queue.write_buffer(&self.vertex_buffer, 0, cast_slice(&vertices));
-        self.vertices_changed = false;
+        self.written_vertices = Some(vertices);
     }
     fn get_quad_size(&self) -> (f32, f32) {
-        let frame_aspect = self.framebuffer.height() as f32 / self.framebuffer.width() as f32;
-        let width = self.config.width as f32;
-        let height = self.config.height as f32;
-        let height_of_width = width * frame_aspect;
-        let width_of_height = height / frame_aspect;
-
-        if height_of_width <= height {
-            (1.0, height_of_width / height)
-        } else {
-            (width_of_height / width, 1.0)
-        }
+        quad_size(
+            self.displayed_size(),
+            (self.config.width, self.config.height),
+            self.scale_mode,
+        )
+    }
+    /// The framebuffer's size as it appears on the surface, after rotation.
+    fn displayed_size(&self) -> (usize, usize) {
+        let size = (self.framebuffer.width(), self.framebuffer.height());
+        self.rotation.rotate_size(size)
     }
 
+    /// The window-space offset of the quad's top left corner and the window pixels per
+    /// displayed framebuffer pixel, as `(left, top, scale_x, scale_y)`.
+    fn quad_transform(&self) -> (f32, f32, f32, f32) {
+        let (quad_width, quad_height) = self.get_quad_size();
+        let (displayed_width, displayed_height) = self.displayed_size();
+        let surface_width = self.config.width as f32;
+        let surface_height = self.config.height as f32;
+        let scale_x = quad_width * surface_width / displayed_width as f32;
+        let scale_y = quad_height * surface_height / displayed_height as f32;
+        let (align_x, align_y) = self.alignment.factors();
+        let left = (1.0 - quad_width) * surface_width * align_x;
+        let top = (1.0 - quad_height) * surface_height * align_y;
+        (left, top, scale_x, scale_y)
+    }
+
+    /// The backend, name and type of the GPU this instance renders with.
+    pub fn adapter_info(&self) -> &AdapterInfo {
+        &self.adapter_info
+    }
+    /// The format of the window surface, which pipelines drawing to it or to
+    /// [`Pixely::render_to_view`] targets must use.
+    pub fn surface_format(&self) -> TextureFormat {
+        self.config.format
+    }
+    pub fn framebuffer_size(&self) -> (usize, usize) {
+        (self.framebuffer.width(), self.framebuffer.height())
+    }
+    /// The size of the surface in physical pixels, which is the window size times the
+    /// scale factor.
+    pub fn surface_size(&self) -> (u32, u32) {
+        (self.config.width, self.config.height)
+    }
     pub fn buffer_mut(&mut self) -> &mut FrameBuffer {
-        self.framebuffer_changed = true;
         &mut self.framebuffer
     }
-    pub fn resize_framebuffer(&mut self, width: usize, height: usize) {
+    /// Lets `f` draw into the region at `(x, y)` of size `w` by `h`, clipped to the framebuffer,
+    /// and schedules exactly that region for upload.
+    pub fn update_region(
+        &mut self,
+        x: usize,
+        y: usize,
+        w: usize,
+        h: usize,
+        f: impl FnOnce(&mut FrameBufferViewMut),
+    ) {
+        let mut view = self.framebuffer.view_mut(x, y, w, h);
+        f(&mut view);
+        view.mark_all_dirty();
+    }
+    pub fn resize_framebuffer(&mut self, width: usize, height: usize) -> Result<(), SizeOverflow> {
+        self.framebuffer = FrameBuffer::try_new(width, height)?;
+        if let Some((buffer, _)) = &mut self.indexed {
+            buffer.resize(width, height)?;
+        }
+        self.texture = None;
+        self.bind_group = None;
+        self.vertices_changed = true;
+        Ok(())
+    }
+    pub fn resize_framebuffer_keep(
+        &mut self,
+        width: usize,
+        height: usize,
+        fill: Pixel,
+    ) -> Result<(), SizeOverflow> {
+        self.framebuffer.resize_keep(width, height, fill)?;
+        if let Some((buffer, _)) = &mut self.indexed {
+            buffer.resize(width, height)?;
+        }
+        self.texture = None;
+        self.bind_group = None;
+        self.vertices_changed = true;
+        Ok(())
+    }
+    /// Like [`Pixely::resize_framebuffer_keep`], but scales the content to the new size with
+    /// [`FrameBuffer::resize_scaled`].
+    pub fn resize_framebuffer_scaled(
+        &mut self,
+        width: usize,
+        height: usize,
+        smooth: bool,
+    ) -> Result<(), SizeOverflow> {
+        self.framebuffer.resize_scaled(width, height, smooth)?;
+        if let Some((buffer, _)) = &mut self.indexed {
+            buffer.resize(width, height)?;
+        }
         self.texture = None;
         self.bind_group = None;
         self.vertices_changed = true;
-        self.framebuffer_changed = true;
-        self.framebuffer = FrameBuffer::new(width, height);
+        Ok(())
+    }
+    /// Swaps in `framebuffer`, for example one drawn on another thread, and returns the old
+    /// one. The whole new buffer is uploaded on the next render.
+    pub fn replace_framebuffer(&mut self, mut framebuffer: FrameBuffer) -> FrameBuffer {
+        let size = (framebuffer.width(), framebuffer.height());
+        if size != (self.framebuffer.width(), self.framebuffer.height()) {
+            if let Some((buffer, _)) = &mut self.indexed {
+                buffer
+                    .resize(size.0, size.1)
+                    .expect("a framebuffer's size always fits its indices");
+            }
+            self.texture = None;
+            self.bind_group = None;
+            self.vertices_changed = true;
+        }
+        framebuffer.mark_all_dirty();
+        std::mem::replace(&mut self.framebuffer, framebuffer)
+    }
+    pub fn set_framebuffer(&mut self, framebuffer: FrameBuffer) {
+        self.replace_framebuffer(framebuffer);
     }
+    /// Leaves a black buffer of the same size in place of the one returned.
+    pub fn take_framebuffer(&mut self) -> FrameBuffer {
+        let blank = FrameBuffer::try_new(self.framebuffer.width(), self.framebuffer.height())
+            .expect("the current framebuffer's size is valid");
+        self.replace_framebuffer(blank)
+    }
+    /// Takes the new window size in the same units as [`WindowDesc::width`].
     pub fn resize_surface(&mut self, width: usize, height: usize) {
+        self.logical_size = (width, height);
+        self.update_surface_size();
+    }
+    /// Sets how many physical pixels make up one unit of window size, reallocating the surface
+    /// at the new resolution. Non-positive or non-finite factors count as `1.0`.
+    pub fn set_scale_factor(&mut self, scale_factor: f64) {
+        self.scale_factor = valid_scale_factor(scale_factor);
+        self.update_surface_size();
+    }
+    fn update_surface_size(&mut self) {
+        let (width, height) = physical_size(self.logical_size, self.scale_factor);
         self.vertices_changed = true;
         self.surface_changed = true;
-        self.config.width = width as u32;
-        self.config.height = height as u32;
+        self.config.width = width;
+        self.config.height = height;
+    }
+    /// Falls back to [`PresentMode::Fifo`] if the surface doesn't support `mode`.
+    pub fn set_present_mode(&mut self, mode: PresentMode) {
+        self.config.present_mode = supported_present_mode(&self.present_modes, mode);
+        self.surface_changed = true;
+    }
+    /// Adds a framebuffer drawn over the main one and all earlier layers, blended by its
+    /// alpha channel. Layers are stretched over the same area as the main framebuffer.
+    pub fn add_layer(&mut self, desc: FrameBufferDesc) -> Result<LayerId, SizeOverflow> {
+        let framebuffer = FrameBuffer::try_new(desc.width, desc.height)?;
+        self.layers.push(Layer::new(framebuffer));
+        self.composition_changed = true;
+        Ok(LayerId(self.layers.len() - 1))
+    }
+    pub fn layer(&self, id: LayerId) -> &FrameBuffer {
+        &self.layers[id.0].framebuffer
     }
-    pub fn render(&mut self, device: &Device, queue: &Queue) -> Result<(), SurfaceError> {
-        if self.config.width == 0 || self.config.height == 0 {
-            return Ok(());
+    pub fn layer_mut(&mut self, id: LayerId) -> &mut FrameBuffer {
+        &mut self.layers[id.0].framebuffer
+    }
+    /// Switches to drawing an [`IndexedFrameBuffer`] the size of the framebuffer, whose colors
+    /// are looked up in its palette on the GPU. Layers and post effects still apply. Resizing
+    /// the framebuffer resizes it too, keeping the palette.
+    pub fn enable_indexed_mode(&mut self, device: &Device) {
+        if self.indexed.is_none() {
+            let buffer =
+                IndexedFrameBuffer::new(self.framebuffer.width(), self.framebuffer.height())
+                    .expect("a framebuffer's size always fits its indices");
+            self.indexed = Some((buffer, PaletteLookup::new(device)));
+            self.composition_changed = true;
         }
-        let texture_recreated = self.texture.is_none();
-        if texture_recreated {
-            self.recreate_texture(device);
+    }
+    /// Goes back to drawing the framebuffer, discarding the indexed buffer.
+    pub fn disable_indexed_mode(&mut self) {
+        if self.indexed.take().is_some() {
+            self.composition_changed = true;
         }
-        if texture_recreated || self.framebuffer_changed {
-            self.upload_texture(queue);
+    }
+    /// The indexed buffer, if [indexed mode](Pixely::enable_indexed_mode) is on.
+    pub fn indexed_buffer_mut(&mut self) -> Option<&mut IndexedFrameBuffer> {
+        self.indexed.as_mut().map(|(buffer, _)| buffer)
+    }
+    pub fn set_layer_visible(&mut self, id: LayerId, visible: bool) {
+        self.layers[id.0].visible = visible;
+        self.composition_changed = true;
+    }
+    /// Makes the next `render` reconfigure the surface and upload everything again, for when
+    /// the GPU-side state can't be trusted anymore, e.g. after the device was lost.
+    pub fn invalidate(&mut self) {
+        self.framebuffer.mark_all_dirty();
+        for layer in &mut self.layers {
+            layer.framebuffer.mark_all_dirty();
         }
-        if self.surface_changed {
-            self.reconfigure_surface(device);
+        if let Some((buffer, _)) = &mut self.indexed {
+            buffer.mark_all_changed();
+        }
+        self.vertices_changed = true;
+        self.written_vertices = None;
+        self.surface_changed = true;
+        self.composition_changed = true;
+    }
+    pub fn damage_rect(&self) -> Option<(usize, usize, usize, usize)> {
+        self.framebuffer.dirty_rect()
+    }
+    /// The region of the surface, as `(x, y, width, height)`, that the next `render` will change.
+    /// Any change to the surface or the quad layout damages the whole surface, as does any frame
+    /// drawn with a [scroll](Pixely::set_scroll) offset, where changes can wrap around.
+    pub fn surface_damage_rect(&self) -> Option<(u32, u32, u32, u32)> {
+        let layers_dirty = self
+            .layers
+            .iter()
+            .any(|layer| layer.visible && layer.framebuffer.dirty_rect().is_some());
+        let indexed_changed = self
+            .indexed
+            .as_ref()
+            .is_some_and(|(buffer, _)| buffer.changed());
+        let changed = self.surface_changed || self.vertices_changed || self.composition_changed;
+        let scrolled = self.scroll != (0.0, 0.0);
+        if changed || scrolled || layers_dirty || indexed_changed || self.post_effect.is_some() {
+            return Some((0, 0, self.config.width, self.config.height));
+        }
+        let framebuffer_size = (self.framebuffer.width(), self.framebuffer.height());
+        let dirty = self
+            .rotation
+            .rotate_rect(self.damage_rect()?, framebuffer_size);
+        let (x, y, width, height) = dirty;
+        let (left, top, scale_x, scale_y) = self.quad_transform();
+
+        let x0 = (left + x as f32 * scale_x).floor() as u32;
+        let y0 = (top + y as f32 * scale_y).floor() as u32;
+        let x1 = ((left + (x + width) as f32 * scale_x).ceil() as u32).min(self.config.width);
+        let y1 = ((top + (y + height) as f32 * scale_y).ceil() as u32).min(self.config.height);
+        Some((x0, y0, x1 - x0, y1 - y0))
+    }
+    /// The framebuffer pixel under the window position `(win_x, win_y)`, or `None` if the
+    /// position falls on the letterbox bars or outside the window. Positions are in the same
+    /// units as [`WindowDesc::width`], not physical pixels.
+    pub fn window_to_framebuffer(&self, win_x: f32, win_y: f32) -> Option<(usize, usize)> {
+        if self.framebuffer.width() == 0 || self.framebuffer.height() == 0 {
+            return None;
+        }
+        let win_x = win_x * self.scale_factor as f32;
+        let win_y = win_y * self.scale_factor as f32;
+        if win_x < 0.0 || win_y < 0.0 {
+            return None;
+        }
+        if win_x >= self.config.width as f32 || win_y >= self.config.height as f32 {
+            return None;
+        }
+
+        let (left, top, scale_x, scale_y) = self.quad_transform();
+        let x = ((win_x - left) / scale_x).floor();
+        let y = ((win_y - top) / scale_y).floor();
+        if x < 0.0 || y < 0.0 {
+            return None;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let (displayed_width, displayed_height) = self.displayed_size();
+        if x >= displayed_width || y >= displayed_height {
+            return None;
+        }
+        let framebuffer_size = (self.framebuffer.width(), self.framebuffer.height());
+        Some(self.rotation.unrotate_point((x, y), framebuffer_size))
+    }
+    /// The window position of the center of framebuffer pixel `(px, py)`, in the units of
+    /// [`Pixely::window_to_framebuffer`].
+    pub fn framebuffer_to_window(&self, px: usize, py: usize) -> (f32, f32) {
+        let scale_factor = self.scale_factor as f32;
+        if self.framebuffer.width() == 0 || self.framebuffer.height() == 0 {
+            return (
+                self.config.width as f32 / 2.0 / scale_factor,
+                self.config.height as f32 / 2.0 / scale_factor,
+            );
+        }
+
+        let (left, top, scale_x, scale_y) = self.quad_transform();
+        let framebuffer_size = (self.framebuffer.width(), self.framebuffer.height());
+        let (px, py) = self.rotation.rotate_point((px, py), framebuffer_size);
+        let x = left + (px + 0.5) * scale_x;
+        let y = top + (py + 0.5) * scale_y;
+        (x / scale_factor, y / scale_factor)
+    }
+    /// Sets both the [minification](Pixely::set_min_filter) and
+    /// [magnification](Pixely::set_mag_filter) filter.
+    pub fn set_scaling_filter(&mut self, device: &Device, filter: ScalingFilter) {
+        self.min_filter = filter;
+        self.mag_filter = filter;
+        self.recreate_sampler(device);
+    }
+    /// The filter used where the framebuffer is shown smaller than its actual size.
+    pub fn set_min_filter(&mut self, device: &Device, filter: ScalingFilter) {
+        self.min_filter = filter;
+        self.recreate_sampler(device);
+    }
+    /// The filter used where the framebuffer is shown larger than its actual size.
+    pub fn set_mag_filter(&mut self, device: &Device, filter: ScalingFilter) {
+        self.mag_filter = filter;
+        self.recreate_sampler(device);
+    }
+    pub fn set_address_mode(&mut self, device: &Device, mode: AddressMode) {
+        self.address_mode = mode;
+        self.recreate_sampler(device);
+    }
+    fn recreate_sampler(&mut self, device: &Device) {
+        self.sampler = create_sampler(device, self.min_filter, self.mag_filter, self.address_mode);
+        self.recreate_bind_group(device);
+        for layer in &mut self.layers {
+            layer.recreate_bind_group(device, &self.bind_group_layout, &self.sampler);
+        }
+        if let Some((_, lookup)) = &mut self.indexed {
+            lookup.recreate_bind_group(device, &self.bind_group_layout, &self.sampler);
+        }
+    }
+    /// Rotates the image on the surface, leaving the framebuffer itself untouched.
+    pub fn set_display_rotation(&mut self, rotation: Rotation) {
+        self.rotation = rotation;
+        self.vertices_changed = true;
+    }
+    /// Caps how many frames per second [`Pixely::render`] presents by waiting before each
+    /// present, for present modes like [`PresentMode::Immediate`] that don't wait for vsync.
+    /// `None`, or a rate that isn't positive, removes the cap.
+    pub fn set_frame_limit(&mut self, fps: Option<f32>) {
+        self.frame_interval = fps
+            .filter(|fps| *fps > 0.0 && fps.is_finite())
+            .map(|fps| Duration::from_secs_f32(1.0 / fps));
+    }
+    pub fn set_scale_mode(&mut self, mode: ScaleMode) {
+        self.scale_mode = mode;
+        self.vertices_changed = true;
+    }
+    /// Shifts the displayed image by `(dx, dy)` framebuffer pixels, so the pixel shown in the
+    /// top left corner is `(dx, dy)`. Meant for the fractional part of a scroll position, with
+    /// the whole pixels blitted into the framebuffer; with [`AddressMode::Repeat`] the image
+    /// wraps around instead of smearing its edge pixels. Layers scroll along with it, and
+    /// [`Pixely::window_to_framebuffer`] ignores the offset.
+    pub fn set_scroll(&mut self, dx: f32, dy: f32) {
+        let finite = |v: f32| if v.is_finite() { v } else { 0.0 };
+        self.scroll = (finite(dx), finite(dy));
+        self.vertices_changed = true;
+    }
+    /// Where the framebuffer sits on the surface when it doesn't fill it, which decides on
+    /// which sides the letterbox bars appear.
+    pub fn set_alignment(&mut self, alignment: Alignment) {
+        self.alignment = alignment;
+        self.vertices_changed = true;
+    }
+    /// Runs `wgsl` over every upscaled frame before it's shown.
+    ///
+    /// The shader must define `@fragment fn fragment_main(input: PostInput) -> @location(0)
+    /// vec4<f32>`, and can use these declarations, which are prepended to it:
+    ///
+    /// ```wgsl
+    /// struct PostUniforms {
+    ///     resolution: vec2<f32>, // the target's size in pixels
+    ///     time: f32,             // seconds since the effect was set
+    /// }
+    /// struct PostInput {
+    ///     @builtin(position) position: vec4<f32>,
+    ///     @location(0) uv: vec2<f32>,
+    /// }
+    /// @group(0) @binding(0) var input_texture: texture_2d<f32>;
+    /// @group(0) @binding(1) var input_sampler: sampler;
+    /// @group(0) @binding(2) var<uniform> uniforms: PostUniforms;
+    /// ```
+    pub fn set_post_effect(&mut self, device: &Device, wgsl: &str) -> Result<(), ShaderError> {
+        self.post_effect = Some(PostEffect::new(device, wgsl, self.config.format)?);
+        self.composition_changed = true;
+        Ok(())
+    }
+    /// Applies scanlines and screen curvature to every frame, or turns them off with `None`.
+    /// This replaces any effect set with [`Pixely::set_post_effect`].
+    pub fn set_crt_effect(&mut self, device: &Device, params: Option<CrtParams>) {
+        match params {
+            Some(params) => self
+                .set_post_effect(device, &crt_shader(params))
+                .expect("the CRT shader is valid"),
+            None => self.clear_post_effect(),
+        }
+    }
+    pub fn clear_post_effect(&mut self) {
+        self.post_effect = None;
+        self.composition_changed = true;
+    }
+    /// The framebuffer and layer textures are recreated on the next render.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        self.color_space = color_space;
+        self.composition_changed = true;
+    }
+    /// The color of the letterbox bars around the framebuffer.
+    pub fn set_clear_color(&mut self, color: Pixel) {
+        self.clear_color = color;
+    }
+    /// Starts measuring how long the GPU spends drawing each frame, see
+    /// [`Pixely::last_gpu_time`]. Requires [`Features::TIMESTAMP_QUERY`] on the device.
+    pub fn enable_timestamps(&mut self, device: &Device) -> Result<(), PixelyError> {
+        if !device.features().contains(Features::TIMESTAMP_QUERY) {
+            return Err(PixelyError::MissingFeatures(Features::TIMESTAMP_QUERY));
+        }
+        if self.gpu_timer.is_none() {
+            self.gpu_timer = Some(GpuTimer::new(device));
+        }
+        Ok(())
+    }
+    /// The GPU time of the most recent frame whose timestamps have been read back, usually a
+    /// frame or two behind. `None` until then, or if timestamps aren't enabled.
+    pub fn last_gpu_time(&self) -> Option<Duration> {
+        self.gpu_timer.as_ref().and_then(GpuTimer::last)
+    }
+    /// Calls `callback` right after [`Pixely::render`] presents a frame, replacing any callback
+    /// set before. Frames that are skipped or fail don't call it.
+    pub fn on_frame_presented(&mut self, callback: Box<dyn FnMut() + Send>) {
+        self.on_frame_presented = Some(Mutex::new(callback));
+    }
+    pub fn clear_frame_presented(&mut self) {
+        self.on_frame_presented = None;
+    }
+    /// In strict mode, `render` reports a zero-sized surface or framebuffer as
+    /// [`PixelyError::Unconfigured`] instead of returning [`RenderStatus::Skipped`]. Headless
+    /// renderers still skip with [`SkipReason::Headless`].
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+    /// Renders the next frame, scaled and letterboxed as on the surface, into memory instead.
+    pub fn capture_frame(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+    ) -> Result<FrameBuffer, CaptureError> {
+        let swap_red_blue = match self.config.format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+            format => return Err(CaptureError::UnsupportedFormat(format)),
+        };
+        if self
+            .skip_reason((self.config.width, self.config.height))
+            .is_some()
+        {
+            return Err(CaptureError::Unconfigured);
+        }
+        self.prepare(device, queue);
+
+        let size = Extent3d {
+            width: self.config.width,
+            height: self.config.height,
+            depth_or_array_layers: 1,
+        };
+        let target = device.create_texture(&TextureDescriptor {
+            label: None,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = target.create_view(&Default::default());
+
+        let row_bytes = size.width * size_of::<Pixel>() as u32;
+        let padded_row_bytes = row_bytes.next_multiple_of(COPY_BYTES_PER_ROW_ALIGNMENT);
+        let readback = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: (padded_row_bytes * size.height) as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut cmd = device.create_command_encoder(&Default::default());
+        self.draw_frame(device, queue, &mut cmd, &view, (size.width, size.height));
+        cmd.copy_texture_to_buffer(
+            target.as_image_copy(),
+            ImageCopyBuffer {
+                buffer: &readback,
+                layout: ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(size.height),
+                },
+            },
+            size,
+        );
+        self.submit(queue, cmd);
+
+        let slice = readback.slice(..);
+        let (sender, receiver) = mpsc::channel();
+        slice.map_async(MapMode::Read, move |result| {
+            let _ = sender.send(result);
+        });
+        device.poll(Maintain::Wait);
+        receiver.recv().map_err(|_| BufferAsyncError)??;
+
+        let mapped = slice.get_mapped_range();
+        let mut bytes = Vec::with_capacity((row_bytes * size.height) as usize);
+        for row in mapped.chunks_exact(padded_row_bytes as usize) {
+            bytes.extend_from_slice(&row[..row_bytes as usize]);
+        }
+        drop(mapped);
+        readback.unmap();
+
+        let mut frame = FrameBuffer::from_bytes(size.width as usize, size.height as usize, &bytes)
+            .expect("the readback buffer holds one pixel per texel");
+        if swap_red_blue {
+            frame.map_pixels(|p| Pixel::rgba(p.blue, p.green, p.red, p.alpha));
+        }
+        Ok(frame)
+    }
+    fn prepare(&mut self, device: &Device, queue: &Queue) {
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.poll(device, queue);
+        }
+        if self.texture_is_stale() {
+            self.recreate_texture(device);
+            self.vertices_changed = true;
+            self.upload_texture(queue);
+        } else {
+            self.upload_dirty_region(queue);
         }
         if self.vertices_changed {
             self.update_vertex_buffer(queue);
         }
+        for layer in &mut self.layers {
+            layer.prepare(
+                device,
+                queue,
+                &self.bind_group_layout,
+                &self.sampler,
+                self.color_space,
+            );
+        }
+        if let Some((buffer, lookup)) = &mut self.indexed {
+            lookup.prepare(
+                device,
+                queue,
+                buffer,
+                &self.bind_group_layout,
+                &self.sampler,
+                self.color_space,
+            );
+        }
+        self.composition_changed = false;
+        // Without a surface there's nothing to reconfigure, so any change already applies.
+        if self.surface.is_none() {
+            self.surface_changed = false;
+        }
+    }
+    fn prepare_msaa_target(&mut self, device: &Device, (width, height): (u32, u32)) {
+        if self.sample_count == 1 {
+            return;
+        }
+        if let Some((texture, _)) = &self.msaa_target {
+            if (texture.width(), texture.height()) == (width, height) {
+                return;
+            }
+        }
 
-        let texture = self.surface.get_current_texture()?;
-        let view = texture.texture.create_view(&Default::default());
-        let mut cmd = device.create_command_encoder(&Default::default());
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: self.sample_count,
+            dimension: TextureDimension::D2,
+            format: self.config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        self.msaa_target = Some((texture, view));
+    }
+    fn draw(&self, cmd: &mut CommandEncoder, view: &TextureView) {
+        let (view, resolve_target) = match &self.msaa_target {
+            Some((_, msaa_view)) => (msaa_view, Some(view)),
+            None => (view, None),
+        };
         let mut pass = cmd.begin_render_pass(&RenderPassDescriptor {
             label: None,
             color_attachments: &[Some(RenderPassColorAttachment {
-                view: &view,
-                resolve_target: None,
+                view,
+                resolve_target,
                 ops: Operations {
-                    store: true,
-                    load: LoadOp::Clear(Color::BLACK),
+                    store: resolve_target.is_none(),
+                    load: LoadOp::Clear(pixel_to_color(self.clear_color, self.color_space)),
                 },
             })],
             depth_stencil_attachment: None,
@@ -316,45 +981,1129 @@ impl Pixely {
         pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
         pass.set_index_buffer(self.index_buffer.slice(..), IndexFormat::Uint16);
         pass.set_pipeline(&self.pipeline);
-        pass.set_bind_group(0, self.bind_group.as_ref().unwrap(), &[]);
+        let bind_group = match &self.indexed {
+            Some((_, lookup)) => lookup.quad_bind_group(),
+            None => self.bind_group.as_ref(),
+        };
+        pass.set_bind_group(0, bind_group.unwrap(), &[]);
         pass.draw_indexed(0..6, 0, 0..1);
 
-        drop(pass);
+        pass.set_pipeline(&self.layer_pipeline);
+        for bind_group in self.layers.iter().filter_map(Layer::bind_group) {
+            pass.set_bind_group(0, bind_group, &[]);
+            pass.draw_indexed(0..6, 0, 0..1);
+        }
+    }
+    fn draw_frame(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        cmd: &mut CommandEncoder,
+        view: &TextureView,
+        size: (u32, u32),
+    ) {
+        self.prepare_msaa_target(device, size);
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.begin(cmd);
+        }
+        if let Some((_, lookup)) = &self.indexed {
+            lookup.draw(cmd);
+        }
+        match &mut self.post_effect {
+            Some(effect) => {
+                effect.prepare(device, queue, size);
+                let effect = self.post_effect.as_ref().unwrap();
+                self.draw(cmd, effect.input_view());
+                effect.draw(cmd, view);
+            }
+            None => self.draw(cmd, view),
+        }
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.end(cmd);
+        }
+    }
+    fn submit(&mut self, queue: &Queue, cmd: CommandEncoder) {
         queue.submit(once(cmd.finish()));
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.submitted();
+        }
+    }
+    fn wait_for_frame_limit(&self) {
+        let (Some(interval), Some(last_present)) = (self.frame_interval, self.last_present) else {
+            return;
+        };
+        let deadline = last_present + interval;
+
+        // Sleeping overshoots by up to a millisecond or so on most systems, so spin for the
+        // rest of the wait.
+        let spin_margin = Duration::from_millis(1);
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining > spin_margin {
+            thread::sleep(remaining - spin_margin);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+    }
+    fn acquire_texture(&mut self, device: &Device) -> Result<SurfaceTexture, SurfaceError> {
+        let surface = self
+            .surface
+            .as_ref()
+            .expect("only windowed renderers present");
+        match surface.get_current_texture() {
+            Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                self.reconfigure_surface(device);
+                self.surface.as_ref().unwrap().get_current_texture()
+            }
+            result => result,
+        }
+    }
+    /// A lost or outdated surface is reconfigured and acquired once more before giving up.
+    ///
+    /// [`SurfaceError::Timeout`] and persisting [`SurfaceError::Lost`] or
+    /// [`SurfaceError::Outdated`] errors are recoverable: skip the frame and render the next
+    /// one. [`SurfaceError::OutOfMemory`] is fatal.
+    pub fn render(&mut self, device: &Device, queue: &Queue) -> Result<RenderStatus, PixelyError> {
+        let skip_reason = self
+            .skip_reason((self.config.width, self.config.height))
+            .or(self.surface.is_none().then_some(SkipReason::Headless));
+        if let Some(reason) = skip_reason {
+            // Having no window isn't a misconfiguration, so strict mode lets it through.
+            return match self.strict && reason != SkipReason::Headless {
+                true => Err(PixelyError::Unconfigured),
+                false => Ok(RenderStatus::Skipped(reason)),
+            };
+        }
+        if self.surface_changed {
+            self.reconfigure_surface(device);
+        }
+
+        let texture = self.acquire_texture(device)?;
+        let status = self.render_into(device, queue, &texture)?;
+        self.wait_for_frame_limit();
         texture.present();
-        Ok(())
+        self.last_present = Some(Instant::now());
+        if let Some(callback) = &mut self.on_frame_presented {
+            let callback = callback.get_mut().unwrap_or_else(|e| e.into_inner());
+            callback();
+        }
+        Ok(status)
+    }
+    /// Does what [`Pixely::render`] does with a surface texture the caller acquired, leaving
+    /// presenting it to them, so it can be shared with other renderers.
+    ///
+    /// The texture must have the same format as [`Pixely::surface_format`].
+    pub fn render_into(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        surface_texture: &SurfaceTexture,
+    ) -> Result<RenderStatus, PixelyError> {
+        let texture = &surface_texture.texture;
+        let view = texture.create_view(&Default::default());
+        self.render_to_view(device, queue, &view, (texture.width(), texture.height()))
+    }
+    /// Draws the framebuffer into `target` instead of the window, scaled to `target_size`.
+    ///
+    /// `target` must have the same format as the window surface.
+    pub fn render_to_view(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        target: &TextureView,
+        target_size: (u32, u32),
+    ) -> Result<RenderStatus, PixelyError> {
+        if let Some(reason) = self.skip_reason(target_size) {
+            return match self.strict {
+                true => Err(PixelyError::Unconfigured),
+                false => Ok(RenderStatus::Skipped(reason)),
+            };
+        }
+        self.prepare(device, queue);
+        if target_size != (self.config.width, self.config.height) {
+            self.write_vertices(
+                queue,
+                quad_size(self.displayed_size(), target_size, self.scale_mode),
+            );
+            self.vertices_changed = true;
+        }
+
+        let mut cmd = device.create_command_encoder(&Default::default());
+        self.draw_frame(device, queue, &mut cmd, target, target_size);
+        self.submit(queue, cmd);
+        Ok(RenderStatus::Presented)
+    }
+}
+
+pub struct Context {
+    pub instance: Instance,
+    pub adapter: Adapter,
+    pub device: Device,
+    pub queue: Queue,
+}
+pub struct ContextDesc {
+    pub power_preference: PowerPreference,
+    pub required_features: Features,
+    /// `None` requests [`Limits::downlevel_defaults`], raised to the adapter's maximum
+    /// texture dimensions.
+    pub required_limits: Option<Limits>,
+}
+impl Default for ContextDesc {
+    fn default() -> Self {
+        Self {
+            power_preference: PowerPreference::HighPerformance,
+            required_features: Features::empty(),
+            required_limits: None,
+        }
+    }
+}
+
+/// Creates the wgpu instance, adapter, device and queue needed for [`Pixely::new`],
+/// picking an adapter that can present to `window`.
+pub async fn create_context<W: HasRawWindowHandle + HasRawDisplayHandle>(
+    window: &W,
+    desc: &ContextDesc,
+) -> Result<Context, ContextError> {
+    let instance = Instance::default();
+    let surface = unsafe { instance.create_surface(window) }?;
+    let adapter = instance
+        .request_adapter(&RequestAdapterOptions {
+            power_preference: desc.power_preference,
+            force_fallback_adapter: false,
+            compatible_surface: Some(&surface),
+        })
+        .await
+        .ok_or(ContextError::NoAdapter)?;
+    drop(surface);
+
+    let limits = device_limits(desc, adapter.features(), adapter.limits())?;
+    let (device, queue) = adapter
+        .request_device(
+            &DeviceDescriptor {
+                label: None,
+                features: desc.required_features,
+                limits,
+            },
+            None,
+        )
+        .await?;
+    Ok(Context {
+        instance,
+        adapter,
+        device,
+        queue,
+    })
+}
+
+/// The limits to request a device with, if an adapter with `features` and `limits` supports
+/// everything `desc` asks for.
+fn device_limits(
+    desc: &ContextDesc,
+    features: Features,
+    limits: Limits,
+) -> Result<Limits, ContextError> {
+    let missing = desc.required_features.difference(features);
+    if !missing.is_empty() {
+        return Err(ContextError::UnsupportedFeatures(missing));
+    }
+    let required = match &desc.required_limits {
+        Some(required) => required.clone(),
+        None => Limits::downlevel_defaults().using_resolution(limits.clone()),
+    };
+    match required.check_limits(&limits) {
+        true => Ok(required),
+        false => Err(ContextError::UnsupportedLimits),
+    }
+}
+
+#[derive(Debug)]
+pub enum ContextError {
+    CreateSurface(CreateSurfaceError),
+    NoAdapter,
+    UnsupportedFeatures(Features),
+    UnsupportedLimits,
+    RequestDevice(RequestDeviceError),
+}
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CreateSurface(e) => write!(f, "failed to create the surface: {e}"),
+            Self::NoAdapter => write!(f, "no adapter can present to the window"),
+            Self::UnsupportedFeatures(features) => {
+                write!(f, "the adapter doesn't support {features:?}")
+            }
+            Self::UnsupportedLimits => write!(f, "the adapter doesn't support the required limits"),
+            Self::RequestDevice(e) => write!(f, "failed to create the device: {e}"),
+        }
+    }
+}
+impl Error for ContextError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CreateSurface(e) => Some(e),
+            Self::RequestDevice(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<CreateSurfaceError> for ContextError {
+    fn from(e: CreateSurfaceError) -> Self {
+        Self::CreateSurface(e)
+    }
+}
+impl From<RequestDeviceError> for ContextError {
+    fn from(e: RequestDeviceError) -> Self {
+        Self::RequestDevice(e)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RenderStatus {
+    Presented,
+    Skipped(SkipReason),
+}
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SkipReason {
+    ZeroSizedSurface,
+    ZeroSizedFramebuffer,
+    /// There's no window to present to; see [`Pixely::new_headless`].
+    Headless,
+}
+
+#[derive(Debug)]
+pub enum CaptureError {
+    Unconfigured,
+    /// Captures can only be read back from 8-bit RGBA or BGRA surfaces.
+    UnsupportedFormat(TextureFormat),
+    Map(BufferAsyncError),
+}
+impl fmt::Display for CaptureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unconfigured => write!(f, "the surface or framebuffer has a size of zero"),
+            Self::UnsupportedFormat(format) => {
+                write!(f, "can't read back frames in the {format:?} format")
+            }
+            Self::Map(e) => write!(f, "failed to read back the captured frame: {e}"),
+        }
+    }
+}
+impl Error for CaptureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Map(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<BufferAsyncError> for CaptureError {
+    fn from(e: BufferAsyncError) -> Self {
+        Self::Map(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum PixelyError {
+    MissingField(&'static str),
+    CreateSurface(CreateSurfaceError),
+    /// The surface reports no formats it can be configured with for this adapter.
+    UnsupportedSurfaceFormat,
+    ShaderCompilation(ShaderError),
+    FrameBufferSize(SizeOverflow),
+    /// The device wasn't created with features this needs.
+    MissingFeatures(Features),
+    Unconfigured,
+    Surface(SurfaceError),
+}
+impl fmt::Display for PixelyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingField(field) => write!(f, "the builder is missing `{field}`"),
+            Self::CreateSurface(e) => write!(f, "failed to create the surface: {e}"),
+            Self::UnsupportedSurfaceFormat => {
+                write!(
+                    f,
+                    "the surface doesn't support any format with this adapter"
+                )
+            }
+            Self::ShaderCompilation(e) => write!(f, "{e}"),
+            Self::FrameBufferSize(e) => write!(f, "{e}"),
+            Self::MissingFeatures(features) => {
+                write!(f, "the device is missing the features {features:?}")
+            }
+            Self::Unconfigured => write!(f, "the surface or framebuffer has a size of zero"),
+            Self::Surface(e) => write!(f, "failed to acquire the surface texture: {e}"),
+        }
+    }
+}
+impl Error for PixelyError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::CreateSurface(e) => Some(e),
+            Self::ShaderCompilation(e) => Some(e),
+            Self::FrameBufferSize(e) => Some(e),
+            Self::Surface(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+impl From<CreateSurfaceError> for PixelyError {
+    fn from(e: CreateSurfaceError) -> Self {
+        Self::CreateSurface(e)
+    }
+}
+impl From<ShaderError> for PixelyError {
+    fn from(e: ShaderError) -> Self {
+        Self::ShaderCompilation(e)
+    }
+}
+impl From<SizeOverflow> for PixelyError {
+    fn from(e: SizeOverflow) -> Self {
+        Self::FrameBufferSize(e)
+    }
+}
+impl From<SurfaceError> for PixelyError {
+    fn from(e: SurfaceError) -> Self {
+        Self::Surface(e)
     }
 }
 
 pub struct PixelyDesc<'a, W> {
     pub window: WindowDesc<'a, W>,
     pub buffer: FrameBufferDesc,
+    pub scaling_filter: ScalingFilter,
+    /// How the framebuffer is sampled past its edges. [`AddressMode::ClampToEdge`] keeps
+    /// linear filtering from blending in pixels from the opposite edge.
+    pub address_mode: AddressMode,
+    pub scale_mode: ScaleMode,
+    pub color_space: ColorSpace,
+    pub alpha_format: AlphaFormat,
+    /// Samples per pixel for anti-aliasing the edges of the quad: 1, 2 or 4. Counts the
+    /// adapter can't render or resolve with the surface format fall back to 1.
+    pub sample_count: u32,
+    /// WGSL source replacing the built-in shader the framebuffer and layers are drawn with.
+    /// It must define `vertex_main` and `fragment_main` with the same inputs, outputs and
+    /// bindings as `shader.wgsl`; otherwise construction fails with
+    /// [`PixelyError::ShaderCompilation`].
+    pub shader: Option<&'a str>,
     pub instance: &'a Instance,
     pub adapter: &'a Adapter,
     pub device: &'a Device,
     pub queue: &'a Queue,
 }
+pub struct HeadlessDesc<'a> {
+    /// The size of captured frames.
+    pub width: usize,
+    pub height: usize,
+    pub buffer: FrameBufferDesc,
+    pub scaling_filter: ScalingFilter,
+    pub address_mode: AddressMode,
+    pub scale_mode: ScaleMode,
+    pub color_space: ColorSpace,
+    pub alpha_format: AlphaFormat,
+    pub sample_count: u32,
+    /// See [`PixelyDesc::shader`].
+    pub shader: Option<&'a str>,
+    pub adapter: &'a Adapter,
+    pub device: &'a Device,
+    pub queue: &'a Queue,
+}
+/// The settings shared by windowed and headless renderers.
+struct Parts<'a> {
+    buffer: FrameBufferDesc,
+    scaling_filter: ScalingFilter,
+    address_mode: AddressMode,
+    scale_mode: ScaleMode,
+    color_space: ColorSpace,
+    alpha_format: AlphaFormat,
+    sample_count: u32,
+    shader: Option<&'a str>,
+    adapter: &'a Adapter,
+    device: &'a Device,
+    queue: &'a Queue,
+}
 pub struct WindowDesc<'a, W> {
     pub window: &'a W,
+    /// The window size in logical units, as used by the windowing system for positions.
     pub width: usize,
     pub height: usize,
+    /// Physical pixels per logical unit. The surface is allocated at `width * scale_factor`
+    /// by `height * scale_factor`, so it stays sharp on high-DPI displays.
+    pub scale_factor: f64,
+    pub present_mode: PresentMode,
+    /// How the window is composited with what's behind it. `PreMultiplied` and `PostMultiplied`
+    /// let the framebuffer's alpha channel show through; pair them with a transparent clear
+    /// color. Unsupported modes fall back to one the surface supports.
+    pub alpha_mode: CompositeAlphaMode,
+}
+// Not derived, which would require `W: Clone` when only the reference is copied.
+impl<W> Clone for WindowDesc<'_, W> {
+    fn clone(&self) -> Self {
+        *self
+    }
 }
+impl<W> Copy for WindowDesc<'_, W> {}
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct FrameBufferDesc {
     pub width: usize,
     pub height: usize,
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ScalingFilter {
+    #[default]
+    Nearest,
+    Linear,
+}
+impl ScalingFilter {
+    fn filter_mode(self) -> FilterMode {
+        match self {
+            Self::Nearest => FilterMode::Nearest,
+            Self::Linear => FilterMode::Linear,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ScaleMode {
+    /// Scale to the largest size that fits the surface while keeping the aspect ratio.
+    #[default]
+    Fit,
+    /// Fill the whole surface, ignoring the aspect ratio.
+    Stretch,
+    /// Scale by the largest whole-number factor that fits, falling back to `Fit`
+    /// if the framebuffer is larger than the surface.
+    IntegerFit,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Alignment {
+    #[default]
+    Center,
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+impl Alignment {
+    /// How much of the free space goes to the left and above the framebuffer.
+    fn factors(self) -> (f32, f32) {
+        match self {
+            Alignment::Center => (0.5, 0.5),
+            Alignment::TopLeft => (0.0, 0.0),
+            Alignment::Top => (0.5, 0.0),
+            Alignment::TopRight => (1.0, 0.0),
+            Alignment::Left => (0.0, 0.5),
+            Alignment::Right => (1.0, 0.5),
+            Alignment::BottomLeft => (0.0, 1.0),
+            Alignment::Bottom => (0.5, 1.0),
+            Alignment::BottomRight => (1.0, 1.0),
+        }
+    }
+}
+
+/// How the alpha channel of the framebuffer and its layers relates to their colors.
+///
+/// Layers are always blended this way. The framebuffer itself is blended over the clear color
+/// when the window is transparent or its colors are `Premultiplied`; with `Straight` colors on
+/// an opaque window, it replaces the clear color and its alpha has no visible effect.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum AlphaFormat {
+    /// Colors are independent of alpha.
+    #[default]
+    Straight,
+    /// Colors are already multiplied by alpha, as produced by [`Pixel::over_premultiplied`].
+    /// Blending these avoids dark fringes around translucent edges.
+    Premultiplied,
+}
+impl AlphaFormat {
+    fn blend_state(self) -> BlendState {
+        match self {
+            Self::Straight => BlendState::ALPHA_BLENDING,
+            Self::Premultiplied => BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        }
+    }
+}
+
+/// How the framebuffer's color values are interpreted.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum ColorSpace {
+    /// Values are sRGB-encoded, like colors picked in most image editors, and reach the
+    /// screen unchanged.
+    #[default]
+    Srgb,
+    /// Values are linear light intensities and get sRGB-encoded on the way to the screen,
+    /// so mid-range values appear brighter than in `Srgb`.
+    Linear,
+}
+impl ColorSpace {
+    fn texture_format(self) -> TextureFormat {
+        match self {
+            Self::Srgb => TextureFormat::Rgba8UnormSrgb,
+            Self::Linear => TextureFormat::Rgba8Unorm,
+        }
+    }
+}
+
+/// A clockwise rotation of the displayed image.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    #[default]
+    None,
+    Cw90,
+    Cw180,
+    Cw270,
+}
+impl Rotation {
+    fn swaps_axes(self) -> bool {
+        matches!(self, Self::Cw90 | Self::Cw270)
+    }
+    fn rotate_size(self, (width, height): (usize, usize)) -> (usize, usize) {
+        match self.swaps_axes() {
+            true => (height, width),
+            false => (width, height),
+        }
+    }
+    /// Where framebuffer pixel `(x, y)` ends up in the rotated image. Out-of-bounds pixels
+    /// map to positions outside of it.
+    fn rotate_point(self, (x, y): (usize, usize), (width, height): (usize, usize)) -> (f32, f32) {
+        let (x, y) = (x as f32, y as f32);
+        let (last_x, last_y) = ((width - 1) as f32, (height - 1) as f32);
+        match self {
+            Self::None => (x, y),
+            Self::Cw90 => (last_y - y, x),
+            Self::Cw180 => (last_x - x, last_y - y),
+            Self::Cw270 => (y, last_x - x),
+        }
+    }
+    /// The framebuffer pixel shown at `(x, y)` of the rotated image, which must be in bounds.
+    fn unrotate_point(
+        self,
+        (x, y): (usize, usize),
+        (width, height): (usize, usize),
+    ) -> (usize, usize) {
+        match self {
+            Self::None => (x, y),
+            Self::Cw90 => (y, height - 1 - x),
+            Self::Cw180 => (width - 1 - x, height - 1 - y),
+            Self::Cw270 => (width - 1 - y, x),
+        }
+    }
+    fn rotate_rect(
+        self,
+        (x, y, rect_width, rect_height): (usize, usize, usize, usize),
+        (width, height): (usize, usize),
+    ) -> (usize, usize, usize, usize) {
+        match self {
+            Self::None => (x, y, rect_width, rect_height),
+            Self::Cw90 => (height - y - rect_height, x, rect_height, rect_width),
+            Self::Cw180 => (
+                width - x - rect_width,
+                height - y - rect_height,
+                rect_width,
+                rect_height,
+            ),
+            Self::Cw270 => (y, width - x - rect_width, rect_height, rect_width),
+        }
+    }
+    /// The texture coordinates for the bottom left, top left, bottom right and top right
+    /// corners of the quad.
+    fn tex_coords(self) -> [[f32; 2]; 4] {
+        let bottom_left = [0.0, 1.0];
+        let top_left = [0.0, 0.0];
+        let bottom_right = [1.0, 1.0];
+        let top_right = [1.0, 0.0];
+        match self {
+            Self::None => [bottom_left, top_left, bottom_right, top_right],
+            Self::Cw90 => [bottom_right, bottom_left, top_right, top_left],
+            Self::Cw180 => [top_right, bottom_right, top_left, bottom_left],
+            Self::Cw270 => [top_left, top_right, bottom_left, bottom_right],
+        }
+    }
+}
+
+fn create_sampler(
+    device: &Device,
+    min_filter: ScalingFilter,
+    mag_filter: ScalingFilter,
+    address_mode: AddressMode,
+) -> Sampler {
+    device.create_sampler(&SamplerDescriptor {
+        label: None,
+        address_mode_u: address_mode,
+        address_mode_v: address_mode,
+        address_mode_w: address_mode,
+        mag_filter: mag_filter.filter_mode(),
+        min_filter: min_filter.filter_mode(),
+        mipmap_filter: FilterMode::Nearest,
+        ..Default::default()
+    })
+}
+/// Clear colors are linear, so in [`ColorSpace::Srgb`] the pixel is decoded the way sampling
+/// the framebuffer texture decodes it, which makes both look the same on the surface.
+fn pixel_to_color(pixel: Pixel, color_space: ColorSpace) -> Color {
+    let channel = |value: u8| {
+        let value = value as f64 / 255.0;
+        match color_space {
+            ColorSpace::Linear => value,
+            ColorSpace::Srgb if value <= 0.04045 => value / 12.92,
+            ColorSpace::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+        }
+    };
+    Color {
+        r: channel(pixel.red),
+        g: channel(pixel.green),
+        b: channel(pixel.blue),
+        a: pixel.alpha as f64 / 255.0,
+    }
+}
+fn supported_present_mode(supported: &[PresentMode], mode: PresentMode) -> PresentMode {
+    match mode {
+        // wgpu resolves the automatic modes to a supported one itself.
+        PresentMode::AutoVsync | PresentMode::AutoNoVsync => mode,
+        _ if supported.contains(&mode) => mode,
+        _ => PresentMode::Fifo,
+    }
+}
+
+/// Prefers an sRGB format so the framebuffer's colors come out unchanged.
+fn preferred_surface_format(supported: &[TextureFormat]) -> Option<TextureFormat> {
+    supported
+        .iter()
+        .copied()
+        .find(TextureFormat::is_srgb)
+        .or_else(|| supported.first().copied())
+}
+/// Falls back to a single sample if the adapter can't render and resolve `requested` samples
+/// of `format`.
+fn supported_sample_count(adapter: &Adapter, format: TextureFormat, requested: u32) -> u32 {
+    let flags = adapter.get_texture_format_features(format).flags;
+    let resolvable = flags.contains(TextureFormatFeatureFlags::MULTISAMPLE_RESOLVE);
+    match requested {
+        2 | 4 if resolvable && flags.sample_count_supported(requested) => requested,
+        _ => 1,
+    }
+}
+fn create_pipeline(
+    device: &Device,
+    layout: &PipelineLayout,
+    shader: &ShaderModule,
+    format: TextureFormat,
+    blend: BlendState,
+    sample_count: u32,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: None,
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vertex_main",
+            buffers: &[VertexBufferLayout {
+                array_stride: size_of::<Vertex>() as u64,
+                step_mode: VertexStepMode::Vertex,
+                attributes: &[
+                    VertexAttribute {
+                        format: VertexFormat::Float32x2,
+                        offset: 0,
+                        shader_location: 0,
+                    },
+                    VertexAttribute {
+                        format: VertexFormat::Float32x2,
+                        offset: 2 * size_of::<f32>() as u64,
+                        shader_location: 1,
+                    },
+                ],
+            }],
+        },
+        primitive: PrimitiveState {
+            topology: PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: FrontFace::Cw,
+            cull_mode: Some(Face::Back),
+            unclipped_depth: false,
+            polygon_mode: PolygonMode::Fill,
+            conservative: false,
+        },
+        depth_stencil: None,
+        multisample: MultisampleState {
+            count: sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point: "fragment_main",
+            targets: &[Some(ColorTargetState {
+                format,
+                blend: Some(blend),
+                write_mask: ColorWrites::ALL,
+            })],
+        }),
+        multiview: None,
+    })
+}
+fn create_framebuffer_texture(
+    device: &Device,
+    framebuffer: &FrameBuffer,
+    color_space: ColorSpace,
+) -> Texture {
+    let format = color_space.texture_format();
+    device.create_texture(&TextureDescriptor {
+        label: None,
+        size: Extent3d {
+            width: framebuffer.width() as u32,
+            height: framebuffer.height() as u32,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format,
+        usage: TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING,
+        view_formats: &[format],
+    })
+}
+fn create_texture_bind_group(
+    device: &Device,
+    layout: &BindGroupLayout,
+    texture: &Texture,
+    sampler: &Sampler,
+) -> BindGroup {
+    let view = texture.create_view(&Default::default());
+    device.create_bind_group(&BindGroupDescriptor {
+        label: None,
+        layout,
+        entries: &[
+            BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(&view),
+            },
+            BindGroupEntry {
+                binding: 1,
+                resource: BindingResource::Sampler(sampler),
+            },
+        ],
+    })
+}
+fn texture_matches(texture: &Texture, framebuffer: &FrameBuffer, color_space: ColorSpace) -> bool {
+    texture.width() as usize == framebuffer.width()
+        && texture.height() as usize == framebuffer.height()
+        && texture.format() == color_space.texture_format()
+}
+fn write_framebuffer_region(
+    queue: &Queue,
+    texture: &Texture,
+    framebuffer: &FrameBuffer,
+    (x, y, width, height): (usize, usize, usize, usize),
+) {
+    let mut image_copy = texture.as_image_copy();
+    image_copy.origin = Origin3d {
+        x: x as u32,
+        y: y as u32,
+        z: 0,
+    };
+    // The source rows keep the framebuffer's full stride; `write_texture` has no
+    // alignment requirement on it, unlike buffer-to-texture copies.
+    let layout = ImageDataLayout {
+        offset: ((y * framebuffer.width() + x) * size_of::<Pixel>()) as u64,
+        bytes_per_row: Some((framebuffer.width() * size_of::<Pixel>()) as u32),
+        rows_per_image: Some(height as u32),
+    };
+    let size = Extent3d {
+        width: width as u32,
+        height: height as u32,
+        depth_or_array_layers: 1,
+    };
+    queue.write_texture(image_copy, framebuffer.as_bytes(), layout, size);
+}
+
+fn valid_scale_factor(scale_factor: f64) -> f64 {
+    match scale_factor.is_finite() && scale_factor > 0.0 {
+        true => scale_factor,
+        false => 1.0,
+    }
+}
+pub(crate) fn physical_size((width, height): (usize, usize), scale_factor: f64) -> (u32, u32) {
+    let scale = |length: usize| (length as f64 * scale_factor).round() as u32;
+    (scale(width), scale(height))
+}
+
+fn supported_alpha_mode(
+    supported: &[CompositeAlphaMode],
+    mode: CompositeAlphaMode,
+) -> CompositeAlphaMode {
+    if supported.contains(&mode) {
+        mode
+    } else {
+        supported
+            .first()
+            .copied()
+            .unwrap_or(CompositeAlphaMode::Opaque)
+    }
+}
+fn blend_state(alpha_mode: CompositeAlphaMode, alpha_format: AlphaFormat) -> BlendState {
+    match (alpha_mode, alpha_format) {
+        (CompositeAlphaMode::PreMultiplied | CompositeAlphaMode::PostMultiplied, _) => {
+            alpha_format.blend_state()
+        }
+        // Over opaque black this matches replacing, and over any other clear color it's what
+        // premultiplied colors are meant for.
+        (_, AlphaFormat::Premultiplied) => BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+        (_, AlphaFormat::Straight) => BlendState::REPLACE,
+    }
+}
+
+fn quad_size(framebuffer: (usize, usize), surface: (u32, u32), mode: ScaleMode) -> (f32, f32) {
+    match mode {
+        ScaleMode::Fit => fit_quad_size(framebuffer, surface),
+        ScaleMode::Stretch => (1.0, 1.0),
+        ScaleMode::IntegerFit => {
+            let scale_x = (surface.0 as usize).checked_div(framebuffer.0).unwrap_or(0);
+            let scale_y = (surface.1 as usize).checked_div(framebuffer.1).unwrap_or(0);
+            let scale = scale_x.min(scale_y);
+            if scale == 0 {
+                return fit_quad_size(framebuffer, surface);
+            }
+
+            let width = (scale * framebuffer.0) as f32 / surface.0 as f32;
+            let height = (scale * framebuffer.1) as f32 / surface.1 as f32;
+            (width, height)
+        }
+    }
+}
+fn fit_quad_size(framebuffer: (usize, usize), surface: (u32, u32)) -> (f32, f32) {
+    let frame_aspect = framebuffer.1 as f32 / framebuffer.0 as f32;
+    let width = surface.0 as f32;
+    let height = surface.1 as f32;
+    let height_of_width = width * frame_aspect;
+    let width_of_height = height / frame_aspect;
+
+    if height_of_width <= height {
+        (1.0, height_of_width / height)
+    } else {
+        (width_of_height / width, 1.0)
+    }
+}
+
 #[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(C)]
 struct Vertex {
     position: [f32; 2],
     tex_coord: [f32; 2],
 }
-unsafe impl Pod for Vertex {}
-unsafe impl Zeroable for Vertex {}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Pod for Vertex {}
+#[cfg(feature = "bytemuck")]
+unsafe impl bytemuck::Zeroable for Vertex {}
+#[cfg(not(feature = "bytemuck"))]
+unsafe impl cast::Plain for Vertex {}
 fn vertex(position: [f32; 2], tex_coord: [f32; 2]) -> Vertex {
     Vertex {
         position,
         tex_coord,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use post::poll_ready;
+
+    struct Gpu {
+        adapter: Adapter,
+        device: Device,
+        queue: Queue,
+    }
+    impl Gpu {
+        /// Any adapter will do, including software ones like llvmpipe. Tests needing one pass
+        /// without checking anything where there's none.
+        fn new() -> Option<Self> {
+            let instance = Instance::default();
+            let adapter = poll_ready(instance.request_adapter(&Default::default())).flatten()?;
+            let (device, queue) =
+                poll_ready(adapter.request_device(&Default::default(), None))?.ok()?;
+            Some(Self {
+                adapter,
+                device,
+                queue,
+            })
+        }
+        fn headless(
+            &self,
+            size: (usize, usize),
+            buffer: (usize, usize),
+            scaling_filter: ScalingFilter,
+            color_space: ColorSpace,
+        ) -> Pixely {
+            Pixely::new_headless(HeadlessDesc {
+                width: size.0,
+                height: size.1,
+                buffer: FrameBufferDesc {
+                    width: buffer.0,
+                    height: buffer.1,
+                },
+                scaling_filter,
+                address_mode: AddressMode::ClampToEdge,
+                scale_mode: ScaleMode::Fit,
+                color_space,
+                alpha_format: AlphaFormat::default(),
+                sample_count: 1,
+                shader: None,
+                adapter: &self.adapter,
+                device: &self.device,
+                queue: &self.queue,
+            })
+            .unwrap()
+        }
+        fn capture(&self, pixely: &mut Pixely) -> FrameBuffer {
+            pixely.capture_frame(&self.device, &self.queue).unwrap()
+        }
+    }
+
+    #[test]
+    fn letterbox_matches_fill() {
+        let Some(gpu) = Gpu::new() else {
+            return;
+        };
+        for color_space in [ColorSpace::Srgb, ColorSpace::Linear] {
+            let mut pixely = gpu.headless((8, 4), (4, 4), ScalingFilter::Nearest, color_space);
+            let gray = Pixel::rgb(128, 128, 128);
+            pixely.buffer_mut().fill(gray);
+            pixely.set_clear_color(gray);
+
+            let frame = gpu.capture(&mut pixely);
+            let (letterbox, fill) = (frame.get_pixel(0, 2), frame.get_pixel(4, 2));
+            assert_eq!(letterbox, fill, "{color_space:?}");
+        }
+    }
+
+    #[test]
+    fn edges_dont_wrap() {
+        let Some(gpu) = Gpu::new() else {
+            return;
+        };
+        let mut pixely = gpu.headless((8, 4), (2, 1), ScalingFilter::Linear, ColorSpace::Srgb);
+        let (red, blue) = (Pixel::rgb(255, 0, 0), Pixel::rgb(0, 0, 255));
+        pixely.buffer_mut().set_pixel(0, 0, red);
+        pixely.buffer_mut().set_pixel(1, 0, blue);
+
+        let frame = gpu.capture(&mut pixely);
+        for y in 0..4 {
+            assert_eq!(frame.get_pixel(0, y), red);
+            assert_eq!(frame.get_pixel(7, y), blue);
+        }
+    }
+
+    #[test]
+    fn headless_damage_covers_only_the_changed_pixel() {
+        let Some(gpu) = Gpu::new() else {
+            return;
+        };
+        let mut pixely = gpu.headless((8, 4), (4, 2), ScalingFilter::Nearest, ColorSpace::Srgb);
+        gpu.capture(&mut pixely);
+        assert_eq!(pixely.surface_damage_rect(), None);
+
+        pixely.buffer_mut().set_pixel(1, 0, Pixel::white());
+        assert_eq!(pixely.surface_damage_rect(), Some((2, 0, 2, 2)));
+    }
+
+    #[test]
+    fn strict_mode_rejects_zero_sizes_but_not_headless() {
+        let Some(gpu) = Gpu::new() else {
+            return;
+        };
+        let mut pixely = gpu.headless((8, 4), (0, 0), ScalingFilter::Nearest, ColorSpace::Srgb);
+        pixely.set_strict(true);
+        let result = pixely.render(&gpu.device, &gpu.queue);
+        assert!(matches!(result, Err(PixelyError::Unconfigured)));
+
+        let mut pixely = gpu.headless((8, 4), (4, 4), ScalingFilter::Nearest, ColorSpace::Srgb);
+        pixely.set_strict(true);
+        let status = pixely.render(&gpu.device, &gpu.queue).unwrap();
+        assert_eq!(status, RenderStatus::Skipped(SkipReason::Headless));
+    }
+
+    #[test]
+    fn zero_target_size_is_skipped() {
+        let Some(gpu) = Gpu::new() else {
+            return;
+        };
+        let mut pixely = gpu.headless((8, 4), (4, 4), ScalingFilter::Nearest, ColorSpace::Srgb);
+        let target = gpu.device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: pixely.surface_format(),
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+        let view = target.create_view(&Default::default());
+
+        let status = pixely.render_to_view(&gpu.device, &gpu.queue, &view, (0, 0));
+        let expected = RenderStatus::Skipped(SkipReason::ZeroSizedSurface);
+        assert_eq!(status.unwrap(), expected);
+    }
+
+    #[test]
+    fn scrolled_damage_covers_the_surface() {
+        let Some(gpu) = Gpu::new() else {
+            return;
+        };
+        let mut pixely = gpu.headless((8, 4), (4, 2), ScalingFilter::Nearest, ColorSpace::Srgb);
+        pixely.set_scroll(1.0, 0.0);
+        gpu.capture(&mut pixely);
+
+        pixely.buffer_mut().set_pixel(1, 0, Pixel::white());
+        assert_eq!(pixely.surface_damage_rect(), Some((0, 0, 8, 4)));
+    }
+
+    #[test]
+    fn unsupported_context_requirements_are_rejected() {
+        let desc = ContextDesc {
+            required_features: Features::TIMESTAMP_QUERY | Features::DEPTH_CLIP_CONTROL,
+            ..Default::default()
+        };
+        let result = device_limits(&desc, Features::DEPTH_CLIP_CONTROL, Limits::default());
+        let missing = Features::TIMESTAMP_QUERY;
+        assert!(matches!(result, Err(ContextError::UnsupportedFeatures(f)) if f == missing));
+
+        let desc = ContextDesc {
+            required_limits: Some(Limits::default()),
+            ..Default::default()
+        };
+        let result = device_limits(&desc, Features::empty(), Limits::downlevel_defaults());
+        assert!(matches!(result, Err(ContextError::UnsupportedLimits)));
+
+        let result = device_limits(
+            &ContextDesc::default(),
+            Features::empty(),
+            Limits::default(),
+        );
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn premultiplied_framebuffer_blends_on_opaque_windows() {
+        let opaque = CompositeAlphaMode::Opaque;
+        let premultiplied = blend_state(opaque, AlphaFormat::Premultiplied);
+        assert_eq!(premultiplied, BlendState::PREMULTIPLIED_ALPHA_BLENDING);
+        assert_eq!(
+            blend_state(opaque, AlphaFormat::Straight),
+            BlendState::REPLACE
+        );
+
+        let transparent = CompositeAlphaMode::PreMultiplied;
+        let straight = blend_state(transparent, AlphaFormat::Straight);
+        assert_eq!(straight, BlendState::ALPHA_BLENDING);
+    }
+}
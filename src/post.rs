@@ -0,0 +1,267 @@
+use crate::cast::cast_slice;
+use std::{
+    error::Error,
+    fmt,
+    future::Future,
+    pin::pin,
+    task::{Context, Poll, Waker},
+    time::Instant,
+};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferBindingType,
+    BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, CommandEncoder, Device,
+    ErrorFilter, Extent3d, FilterMode, FragmentState, LoadOp, MultisampleState, Operations,
+    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPassColorAttachment,
+    RenderPassDescriptor, RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType,
+    SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, Texture,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureSampleType, TextureUsages,
+    TextureView, TextureViewDimension, VertexState,
+};
+
+const PRELUDE: &str = include_str!("post.wgsl");
+
+/// A fragment shader run over the upscaled frame before it reaches the surface.
+pub(crate) struct PostEffect {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    uniforms: Buffer,
+    sampler: Sampler,
+    format: TextureFormat,
+    started: Instant,
+    input: Option<(Texture, TextureView, BindGroup)>,
+}
+impl PostEffect {
+    pub(crate) fn new(
+        device: &Device,
+        wgsl: &str,
+        format: TextureFormat,
+    ) -> Result<Self, ShaderError> {
+        let source = format!("{PRELUDE}\n{wgsl}");
+
+        device.push_error_scope(ErrorFilter::Validation);
+        let module = device.create_shader_module(ShaderModuleDescriptor {
+            label: None,
+            source: ShaderSource::Wgsl(source.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: None,
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: true },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: None,
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &module,
+                entry_point: "post_vertex",
+                buffers: &[],
+            },
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            fragment: Some(FragmentState {
+                module: &module,
+                entry_point: "fragment_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            multiview: None,
+        });
+        pop_shader_errors(device)?;
+
+        let uniforms = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: 4 * std::mem::size_of::<f32>() as u64,
+            usage: BufferUsages::COPY_DST | BufferUsages::UNIFORM,
+            mapped_at_creation: false,
+        });
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: None,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            pipeline,
+            bind_group_layout,
+            uniforms,
+            sampler,
+            format,
+            started: Instant::now(),
+            input: None,
+        })
+    }
+
+    /// Makes sure the texture the frame is first drawn into matches `size`.
+    pub(crate) fn prepare(&mut self, device: &Device, queue: &Queue, size: (u32, u32)) {
+        let stale = match &self.input {
+            Some((texture, _, _)) => (texture.width(), texture.height()) != size,
+            None => true,
+        };
+        if stale {
+            self.recreate_input(device, size);
+        }
+
+        let time = self.started.elapsed().as_secs_f32();
+        let uniforms = [size.0 as f32, size.1 as f32, time, 0.0];
+        queue.write_buffer(&self.uniforms, 0, cast_slice(&uniforms));
+    }
+    pub(crate) fn input_view(&self) -> &TextureView {
+        let (_, view, _) = self.input.as_ref().unwrap();
+        view
+    }
+    pub(crate) fn draw(&self, cmd: &mut CommandEncoder, view: &TextureView) {
+        let (_, _, bind_group) = self.input.as_ref().unwrap();
+        let mut pass = cmd.begin_render_pass(&RenderPassDescriptor {
+            label: None,
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    store: true,
+                    load: LoadOp::Load,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, bind_group, &[]);
+        pass.draw(0..3, 0..1);
+    }
+
+    fn recreate_input(&mut self, device: &Device, (width, height): (u32, u32)) {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: None,
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: self.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&Default::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: None,
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&self.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: self.uniforms.as_entire_binding(),
+                },
+            ],
+        });
+        self.input = Some((texture, view, bind_group));
+    }
+}
+
+/// Ends a [`ErrorFilter::Validation`] scope pushed before creating shaders and pipelines,
+/// returning the first error raised in it.
+pub(crate) fn pop_shader_errors(device: &Device) -> Result<(), ShaderError> {
+    match poll_ready(device.pop_error_scope()) {
+        Some(Some(error)) => Err(ShaderError(error)),
+        _ => Ok(()),
+    }
+}
+/// Native backends resolve error scopes immediately, so there's no need for an executor.
+pub(crate) fn poll_ready<F: Future>(future: F) -> Option<F::Output> {
+    let future = pin!(future);
+    match future.poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => Some(output),
+        Poll::Pending => None,
+    }
+}
+
+#[derive(Debug)]
+pub struct ShaderError(wgpu::Error);
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "failed to compile the shader: {}", self.0)
+    }
+}
+impl Error for ShaderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+/// Settings for [`crate::Pixely::set_crt_effect`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CrtParams {
+    /// How much the scanlines darken the image, from `0.0` (not at all) to `1.0` (fully).
+    pub scanline_intensity: f32,
+    /// How strongly the image is bent like a curved screen, from `0.0` (flat) to `1.0`.
+    pub curvature: f32,
+}
+impl Default for CrtParams {
+    fn default() -> Self {
+        Self {
+            scanline_intensity: 0.4,
+            curvature: 0.0,
+        }
+    }
+}
+
+pub(crate) fn crt_shader(params: CrtParams) -> String {
+    format!(
+        "const SCANLINE_INTENSITY: f32 = {:?};\nconst CURVATURE: f32 = {:?};\n{}",
+        unit_range(params.scanline_intensity),
+        unit_range(params.curvature),
+        include_str!("crt.wgsl"),
+    )
+}
+/// Clamps to `0.0..=1.0`, turning NaN into zero, which would otherwise produce invalid WGSL.
+fn unit_range(value: f32) -> f32 {
+    match value.is_nan() {
+        true => 0.0,
+        false => value.clamp(0.0, 1.0),
+    }
+}
@@ -0,0 +1,23 @@
+struct PostUniforms {
+    resolution: vec2<f32>,
+    time: f32,
+}
+
+struct PostInput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+@group(0) @binding(0) var input_texture: texture_2d<f32>;
+@group(0) @binding(1) var input_sampler: sampler;
+@group(0) @binding(2) var<uniform> uniforms: PostUniforms;
+
+// A single triangle covering the whole target.
+@vertex
+fn post_vertex(@builtin(vertex_index) index: u32) -> PostInput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: PostInput;
+    out.position = vec4<f32>(uv * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0), 0.0, 1.0);
+    out.uv = uv;
+    return out;
+}
@@ -0,0 +1,247 @@
+use crate::{
+    create_context,
+    framebuffer::{FrameBuffer, SizeOverflow},
+    physical_size, quad_size, valid_scale_factor, AlphaFormat, ColorSpace, ContextDesc,
+    FrameBufferDesc, Pixely, PixelyDesc, PixelyError, RenderStatus, ScaleMode, ScalingFilter,
+    WindowDesc,
+};
+use raw_window_handle::{HasRawDisplayHandle, HasRawWindowHandle};
+use softbuffer::{Context, SoftBufferError, Surface};
+use std::{error::Error, fmt, num::NonZeroU32};
+use wgpu::AddressMode;
+
+/// A CPU-only presenter with the same interface as [`crate::Pixely`], for systems where wgpu
+/// can't initialize.
+///
+/// Every frame is scaled on the CPU and copied to the window in full, so this is far slower
+/// than the GPU path for large windows. Shader-based options have no effect here.
+pub struct SoftwarePixely {
+    framebuffer: FrameBuffer,
+
+    surface: Surface,
+    width: u32,
+    height: u32,
+    scale_factor: f64,
+    surface_changed: bool,
+}
+impl SoftwarePixely {
+    pub fn new<W: HasRawWindowHandle + HasRawDisplayHandle>(
+        window: WindowDesc<W>,
+        buffer: FrameBufferDesc,
+    ) -> Result<Self, SoftwareError> {
+        let framebuffer = FrameBuffer::try_new(buffer.width, buffer.height)?;
+        let context = unsafe { Context::new(window.window) }?;
+        let surface = unsafe { Surface::new(&context, window.window) }?;
+
+        let scale_factor = valid_scale_factor(window.scale_factor);
+        let (width, height) = physical_size((window.width, window.height), scale_factor);
+        Ok(Self {
+            framebuffer,
+            surface,
+            width,
+            height,
+            scale_factor,
+            surface_changed: true,
+        })
+    }
+
+    pub fn buffer_mut(&mut self) -> &mut FrameBuffer {
+        &mut self.framebuffer
+    }
+    pub fn resize_framebuffer(&mut self, width: usize, height: usize) -> Result<(), SizeOverflow> {
+        self.framebuffer = FrameBuffer::try_new(width, height)?;
+        Ok(())
+    }
+    pub fn resize_surface(&mut self, width: usize, height: usize) {
+        self.surface_changed = true;
+        (self.width, self.height) = physical_size((width, height), self.scale_factor);
+    }
+    pub fn render(&mut self) -> Result<(), SoftBufferError> {
+        let (Some(width), Some(height)) =
+            (NonZeroU32::new(self.width), NonZeroU32::new(self.height))
+        else {
+            return Ok(());
+        };
+        if self.surface_changed {
+            self.surface.resize(width, height)?;
+            self.surface_changed = false;
+        }
+
+        let mut buffer = self.surface.buffer_mut()?;
+        blit_scaled(&self.framebuffer, (self.width, self.height), &mut buffer);
+        buffer.present()?;
+        self.framebuffer.clear_dirty();
+        Ok(())
+    }
+}
+
+impl Pixely {
+    /// Creates a [`Pixely`] with default settings and its own wgpu context, or a
+    /// [`SoftwarePixely`] if no adapter can present to the window or the renderer fails to
+    /// initialize.
+    pub async fn new_or_software<W: HasRawWindowHandle + HasRawDisplayHandle>(
+        window: WindowDesc<'_, W>,
+        buffer: FrameBufferDesc,
+    ) -> Result<AnyPixely, SoftwareError> {
+        if let Ok(context) = create_context(window.window, &ContextDesc::default()).await {
+            let pixely = Pixely::new(PixelyDesc {
+                window,
+                buffer,
+                scaling_filter: ScalingFilter::default(),
+                address_mode: AddressMode::ClampToEdge,
+                scale_mode: ScaleMode::default(),
+                color_space: ColorSpace::default(),
+                alpha_format: AlphaFormat::default(),
+                sample_count: 1,
+                shader: None,
+                instance: &context.instance,
+                adapter: &context.adapter,
+                device: &context.device,
+                queue: &context.queue,
+            });
+            if let Ok(pixely) = pixely {
+                let pixely = Box::new(pixely);
+                return Ok(AnyPixely::Gpu { pixely, context });
+            }
+        }
+
+        SoftwarePixely::new(window, buffer).map(AnyPixely::Software)
+    }
+}
+
+/// Either presenter, as created by [`Pixely::new_or_software`]. Match on it to use what only
+/// the GPU renderer supports.
+pub enum AnyPixely {
+    Gpu {
+        pixely: Box<Pixely>,
+        context: crate::Context,
+    },
+    Software(SoftwarePixely),
+}
+impl AnyPixely {
+    pub fn is_software(&self) -> bool {
+        matches!(self, Self::Software(_))
+    }
+    pub fn buffer_mut(&mut self) -> &mut FrameBuffer {
+        match self {
+            Self::Gpu { pixely, .. } => pixely.buffer_mut(),
+            Self::Software(software) => software.buffer_mut(),
+        }
+    }
+    pub fn resize_framebuffer(&mut self, width: usize, height: usize) -> Result<(), SizeOverflow> {
+        match self {
+            Self::Gpu { pixely, .. } => pixely.resize_framebuffer(width, height),
+            Self::Software(software) => software.resize_framebuffer(width, height),
+        }
+    }
+    pub fn resize_surface(&mut self, width: usize, height: usize) {
+        match self {
+            Self::Gpu { pixely, .. } => pixely.resize_surface(width, height),
+            Self::Software(software) => software.resize_surface(width, height),
+        }
+    }
+    /// Skipped frames are only reported by the GPU renderer; the software one presents
+    /// nothing in their place.
+    pub fn render(&mut self) -> Result<RenderStatus, AnyRenderError> {
+        match self {
+            Self::Gpu { pixely, context } => pixely
+                .render(&context.device, &context.queue)
+                .map_err(AnyRenderError::Gpu),
+            Self::Software(software) => software
+                .render()
+                .map(|()| RenderStatus::Presented)
+                .map_err(AnyRenderError::Software),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum AnyRenderError {
+    Gpu(PixelyError),
+    Software(SoftBufferError),
+}
+impl fmt::Display for AnyRenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gpu(e) => e.fmt(f),
+            Self::Software(e) => e.fmt(f),
+        }
+    }
+}
+impl Error for AnyRenderError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Gpu(e) => Some(e),
+            Self::Software(e) => Some(e),
+        }
+    }
+}
+
+/// Why a [`SoftwarePixely`] couldn't be created.
+#[derive(Debug)]
+pub enum SoftwareError {
+    Surface(SoftBufferError),
+    FrameBufferSize(SizeOverflow),
+}
+impl fmt::Display for SoftwareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Surface(e) => e.fmt(f),
+            Self::FrameBufferSize(e) => e.fmt(f),
+        }
+    }
+}
+impl Error for SoftwareError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Surface(e) => Some(e),
+            Self::FrameBufferSize(e) => Some(e),
+        }
+    }
+}
+impl From<SoftBufferError> for SoftwareError {
+    fn from(e: SoftBufferError) -> Self {
+        Self::Surface(e)
+    }
+}
+impl From<SizeOverflow> for SoftwareError {
+    fn from(e: SizeOverflow) -> Self {
+        Self::FrameBufferSize(e)
+    }
+}
+
+fn blit_scaled(framebuffer: &FrameBuffer, surface: (u32, u32), target: &mut [u32]) {
+    let width = surface.0 as usize;
+    let height = surface.1 as usize;
+    let frame_width = framebuffer.width();
+    let frame_height = framebuffer.height();
+    if frame_width == 0 || frame_height == 0 {
+        target.fill(0);
+        return;
+    }
+
+    let (quad_width, quad_height) = quad_size((frame_width, frame_height), surface, ScaleMode::Fit);
+    let image_width = ((quad_width * width as f32).round() as usize).min(width);
+    let image_height = ((quad_height * height as f32).round() as usize).min(height);
+    let left = (width - image_width) / 2;
+    let top = (height - image_height) / 2;
+
+    let pixels = framebuffer.pixels();
+    for (y, row) in target.chunks_exact_mut(width).enumerate() {
+        let src_y = match y.checked_sub(top).filter(|y| *y < image_height) {
+            Some(y) => y * frame_height / image_height,
+            None => {
+                row.fill(0);
+                continue;
+            }
+        };
+
+        let src_row = &pixels[src_y * frame_width..(src_y + 1) * frame_width];
+        for (x, out) in row.iter_mut().enumerate() {
+            *out = match x.checked_sub(left).filter(|x| *x < image_width) {
+                Some(x) => src_row[x * frame_width / image_width].to_argb_u32() & 0x00FF_FFFF,
+                None => 0,
+            };
+        }
+    }
+}
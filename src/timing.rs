@@ -0,0 +1,112 @@
+use std::{
+    sync::{Arc, OnceLock},
+    time::Duration,
+};
+use wgpu::{
+    Buffer, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder, Device, Maintain,
+    MapMode, QuerySet, QuerySetDescriptor, QueryType, Queue, QUERY_SIZE,
+};
+
+const QUERY_BYTES: u64 = 2 * QUERY_SIZE as u64;
+
+/// Measures how long the GPU takes to draw a frame with a pair of timestamp queries.
+///
+/// Results are read back asynchronously, so they lag a frame or more behind, and frames drawn
+/// while a readback is still in flight aren't measured.
+pub(crate) struct GpuTimer {
+    queries: QuerySet,
+    resolved: Buffer,
+    readback: Buffer,
+    /// Recording the current frame, whose results can't be copied into `readback` yet.
+    recording: bool,
+    /// Filled in by the readback's map callback. Unlike a channel, this keeps [`crate::Pixely`]
+    /// `Sync`.
+    pending: Option<Arc<OnceLock<Result<(), BufferAsyncError>>>>,
+    last: Option<Duration>,
+}
+impl GpuTimer {
+    pub(crate) fn new(device: &Device) -> Self {
+        let queries = device.create_query_set(&QuerySetDescriptor {
+            label: None,
+            ty: QueryType::Timestamp,
+            count: 2,
+        });
+        let resolved = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: QUERY_BYTES,
+            usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&BufferDescriptor {
+            label: None,
+            size: QUERY_BYTES,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Self {
+            queries,
+            resolved,
+            readback,
+            recording: false,
+            pending: None,
+            last: None,
+        }
+    }
+
+    pub(crate) fn begin(&mut self, cmd: &mut CommandEncoder) {
+        self.recording = self.pending.is_none();
+        if self.recording {
+            cmd.write_timestamp(&self.queries, 0);
+        }
+    }
+    pub(crate) fn end(&mut self, cmd: &mut CommandEncoder) {
+        if !self.recording {
+            return;
+        }
+        cmd.write_timestamp(&self.queries, 1);
+        cmd.resolve_query_set(&self.queries, 0..2, &self.resolved, 0);
+        cmd.copy_buffer_to_buffer(&self.resolved, 0, &self.readback, 0, QUERY_BYTES);
+    }
+    /// Starts reading back the timestamps of a frame recorded since the last call.
+    pub(crate) fn submitted(&mut self) {
+        if !std::mem::take(&mut self.recording) {
+            return;
+        }
+
+        let pending = Arc::new(OnceLock::new());
+        let mapped = pending.clone();
+        self.readback
+            .slice(..)
+            .map_async(MapMode::Read, move |result| {
+                let _ = mapped.set(result);
+            });
+        self.pending = Some(pending);
+    }
+    /// Picks up the result of a finished readback without waiting for one in flight.
+    pub(crate) fn poll(&mut self, device: &Device, queue: &Queue) {
+        let Some(pending) = &self.pending else {
+            return;
+        };
+        device.poll(Maintain::Poll);
+        let Some(ok) = pending.get().map(Result::is_ok) else {
+            return;
+        };
+        self.pending = None;
+        if !ok {
+            return;
+        }
+
+        let slice = self.readback.slice(..);
+        let mapped = slice.get_mapped_range();
+        let [start, end]: [u64; 2] = crate::cast::pod_read_unaligned(&mapped);
+        drop(mapped);
+        self.readback.unmap();
+
+        let nanos = end.saturating_sub(start) as f64 * queue.get_timestamp_period() as f64;
+        self.last = Some(Duration::from_nanos(nanos as u64));
+    }
+    pub(crate) fn last(&self) -> Option<Duration> {
+        self.last
+    }
+}
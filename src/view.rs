@@ -0,0 +1,148 @@
+use crate::{
+    canvas::Canvas,
+    framebuffer::{FrameBuffer, OutOfBounds, Pixel},
+};
+use std::ops::Range;
+
+/// A rectangular region of a [`FrameBuffer`], addressed in coordinates local to the region.
+#[derive(Copy, Clone)]
+pub struct FrameBufferView<'a> {
+    buffer: &'a FrameBuffer,
+    region: Region,
+}
+impl<'a> FrameBufferView<'a> {
+    pub(crate) fn new(buffer: &'a FrameBuffer, x: usize, y: usize, w: usize, h: usize) -> Self {
+        let region = Region::clipped(buffer, x, y, w, h);
+        Self { buffer, region }
+    }
+
+    pub fn width(&self) -> usize {
+        self.region.width
+    }
+    pub fn height(&self) -> usize {
+        self.region.height
+    }
+    pub fn get_pixel(&self, x: usize, y: usize) -> Pixel {
+        let (x, y) = self
+            .region
+            .buffer_coords(x, y)
+            .unwrap_or_else(|e| panic!("{e}"));
+        self.buffer.get_pixel(x, y)
+    }
+    pub fn get_pixel_checked(&self, x: usize, y: usize) -> Option<Pixel> {
+        let (x, y) = self.region.buffer_coords(x, y).ok()?;
+        self.buffer.get_pixel_checked(x, y)
+    }
+}
+
+/// A mutable rectangular region of a [`FrameBuffer`], addressed in coordinates local to the
+/// region. Drawing through it never touches pixels outside the region.
+pub struct FrameBufferViewMut<'a> {
+    buffer: &'a mut FrameBuffer,
+    region: Region,
+}
+impl<'a> FrameBufferViewMut<'a> {
+    pub(crate) fn new(buffer: &'a mut FrameBuffer, x: usize, y: usize, w: usize, h: usize) -> Self {
+        let region = Region::clipped(buffer, x, y, w, h);
+        Self { buffer, region }
+    }
+
+    pub fn width(&self) -> usize {
+        self.region.width
+    }
+    pub fn height(&self) -> usize {
+        self.region.height
+    }
+    pub fn get_pixel(&self, x: usize, y: usize) -> Pixel {
+        let (x, y) = self
+            .region
+            .buffer_coords(x, y)
+            .unwrap_or_else(|e| panic!("{e}"));
+        self.buffer.get_pixel(x, y)
+    }
+    pub fn get_pixel_checked(&self, x: usize, y: usize) -> Option<Pixel> {
+        let (x, y) = self.region.buffer_coords(x, y).ok()?;
+        self.buffer.get_pixel_checked(x, y)
+    }
+    pub fn set_pixel(&mut self, x: usize, y: usize, pixel: Pixel) {
+        self.set_pixel_checked(x, y, pixel)
+            .unwrap_or_else(|e| panic!("{e}"));
+    }
+    pub fn set_pixel_checked(
+        &mut self,
+        x: usize,
+        y: usize,
+        pixel: Pixel,
+    ) -> Result<(), OutOfBounds> {
+        let (x, y) = self.region.buffer_coords(x, y)?;
+        self.buffer.set_pixel_checked(x, y, pixel)
+    }
+    pub fn as_view(&self) -> FrameBufferView<'_> {
+        FrameBufferView {
+            buffer: self.buffer,
+            region: self.region,
+        }
+    }
+    /// Marks the whole region dirty in the underlying buffer, not just the pixels written.
+    pub fn mark_all_dirty(&mut self) {
+        let Region {
+            x,
+            y,
+            width,
+            height,
+        } = self.region;
+        self.buffer.mark_dirty(x, y, width, height);
+    }
+}
+impl Canvas for FrameBufferViewMut<'_> {
+    fn width(&self) -> usize {
+        self.region.width
+    }
+    fn height(&self) -> usize {
+        self.region.height
+    }
+    fn get_pixel_checked(&self, x: usize, y: usize) -> Option<Pixel> {
+        FrameBufferViewMut::get_pixel_checked(self, x, y)
+    }
+    fn set_pixel_checked(&mut self, x: usize, y: usize, pixel: Pixel) -> Result<(), OutOfBounds> {
+        FrameBufferViewMut::set_pixel_checked(self, x, y, pixel)
+    }
+    fn fill_row(&mut self, y: usize, xs: Range<usize>, pixel: Pixel) {
+        let offset = self.region.x;
+        let xs = xs.start + offset..xs.end + offset;
+        self.buffer.fill_row(y + self.region.y, xs, pixel);
+    }
+}
+
+/// A region already clipped to the buffer it refers to.
+#[derive(Copy, Clone)]
+struct Region {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+impl Region {
+    fn clipped(buffer: &FrameBuffer, x: usize, y: usize, w: usize, h: usize) -> Self {
+        let x = x.min(buffer.width());
+        let y = y.min(buffer.height());
+        Self {
+            x,
+            y,
+            width: w.min(buffer.width() - x),
+            height: h.min(buffer.height() - y),
+        }
+    }
+    fn buffer_coords(self, x: usize, y: usize) -> Result<(usize, usize), OutOfBounds> {
+        if x < self.width && y < self.height {
+            Ok((self.x + x, self.y + y))
+        } else {
+            Err(OutOfBounds {
+                x,
+                y,
+                width: self.width,
+                height: self.height,
+            })
+        }
+    }
+}